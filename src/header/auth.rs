@@ -0,0 +1,864 @@
+use super::*;
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512_256};
+use std::fmt;
+
+/// Hash algorithm of a digest challenge (RFC 2617 and RFC 8760).
+#[derive(PartialEq, Debug, Clone)]
+pub enum Algorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+    Sha512_256,
+    Sha512_256Sess,
+    Unknown(String),
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Algorithm::Md5 => "MD5",
+            Algorithm::Md5Sess => "MD5-sess",
+            Algorithm::Sha256 => "SHA-256",
+            Algorithm::Sha256Sess => "SHA-256-sess",
+            Algorithm::Sha512_256 => "SHA-512-256",
+            Algorithm::Sha512_256Sess => "SHA-512-256-sess",
+            Algorithm::Unknown(a) => a,
+        }
+    }
+
+    /// Whether H(A1) also depends on the nonce and cnonce.
+    pub fn is_sess(&self) -> bool {
+        matches!(
+            self,
+            Algorithm::Md5Sess | Algorithm::Sha256Sess | Algorithm::Sha512_256Sess
+        )
+    }
+
+    /// Hex encoded hash of `data`. `None` for unknown algorithms.
+    pub fn hash(&self, data: &[u8]) -> Option<String> {
+        let hash = match self {
+            Algorithm::Md5 | Algorithm::Md5Sess => Md5::digest(data).to_vec(),
+            Algorithm::Sha256 | Algorithm::Sha256Sess => Sha256::digest(data).to_vec(),
+            Algorithm::Sha512_256 | Algorithm::Sha512_256Sess => Sha512_256::digest(data).to_vec(),
+            Algorithm::Unknown(_) => return None,
+        };
+
+        Some(hash.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// Algorithm names are case-insensitive.
+impl From<&str> for Algorithm {
+    fn from(algorithm: &str) -> Algorithm {
+        match algorithm.to_ascii_uppercase().as_str() {
+            "MD5" => Algorithm::Md5,
+            "MD5-SESS" => Algorithm::Md5Sess,
+            "SHA-256" => Algorithm::Sha256,
+            "SHA-256-SESS" => Algorithm::Sha256Sess,
+            "SHA-512-256" => Algorithm::Sha512_256,
+            "SHA-512-256-SESS" => Algorithm::Sha512_256Sess,
+            _ => Algorithm::Unknown(algorithm.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Quality of protection of a digest.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Qop {
+    Auth,
+    AuthInt,
+    Unknown(String),
+}
+
+impl Qop {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Qop::Auth => "auth",
+            Qop::AuthInt => "auth-int",
+            Qop::Unknown(q) => q,
+        }
+    }
+}
+
+impl From<&str> for Qop {
+    fn from(qop: &str) -> Qop {
+        match qop.to_ascii_lowercase().as_str() {
+            "auth" => Qop::Auth,
+            "auth-int" => Qop::AuthInt,
+            _ => Qop::Unknown(qop.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Qop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Digest challenge sent by the server on `WWW-Authenticate`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub domain: Option<String>,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub stale: bool,
+    pub algorithm: Option<Algorithm>,
+    pub qop: Vec<Qop>,
+}
+
+impl DigestChallenge {
+    fn from_params(params: &Params) -> Option<DigestChallenge> {
+        Some(DigestChallenge {
+            realm: params.get("realm")?.to_owned(),
+            domain: params.get("domain").map(|d| d.to_owned()),
+            nonce: params.get("nonce")?.to_owned(),
+            opaque: params.get("opaque").map(|o| o.to_owned()),
+            stale: params
+                .get("stale")
+                .is_some_and(|s| s.eq_ignore_ascii_case("true")),
+            algorithm: params.get("algorithm").map(Algorithm::from),
+            qop: params
+                .get("qop")
+                .map(|q| {
+                    q.split(',')
+                        .map(|q| Qop::from(q.trim()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Writes the challenge in wire format, including the `Digest` scheme.
+impl fmt::Display for DigestChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Digest realm=\"{}\"", escape_quoted(&self.realm))?;
+
+        if let Some(domain) = &self.domain {
+            write!(f, ", domain=\"{}\"", escape_quoted(domain))?;
+        }
+
+        write!(f, ", nonce=\"{}\"", escape_quoted(&self.nonce))?;
+
+        if let Some(opaque) = &self.opaque {
+            write!(f, ", opaque=\"{}\"", escape_quoted(opaque))?;
+        }
+
+        if self.stale {
+            write!(f, ", stale=TRUE")?;
+        }
+
+        if let Some(algorithm) = &self.algorithm {
+            write!(f, ", algorithm={}", algorithm)?;
+        }
+
+        if !self.qop.is_empty() {
+            let qop = self.qop.iter().map(|q| q.as_str()).collect::<Vec<_>>();
+            write!(f, ", qop=\"{}\"", qop.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Digest credentials sent by the client on `Authorization`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DigestCredentials {
+    pub username: String,
+    pub realm: String,
+    pub nonce: String,
+    pub uri: String,
+    pub response: String,
+    pub algorithm: Option<Algorithm>,
+    pub cnonce: Option<String>,
+    pub opaque: Option<String>,
+    pub qop: Option<Qop>,
+    pub nc: Option<u32>,
+}
+
+impl DigestCredentials {
+    /// Credentials answering `challenge`, still without a response. When a qop is offered,
+    /// `auth` is preferred over `auth-int`, since the body may still change, and `cnonce` and
+    /// `nc` are sent as well.
+    pub fn for_challenge(
+        challenge: &DigestChallenge,
+        username: &str,
+        uri: &str,
+        cnonce: &str,
+        nc: u32,
+    ) -> DigestCredentials {
+        let qop = [Qop::Auth, Qop::AuthInt]
+            .iter()
+            .find(|q| challenge.qop.contains(q))
+            .cloned();
+        let uses_cnonce =
+            qop.is_some() || challenge.algorithm.as_ref().is_some_and(|a| a.is_sess());
+
+        DigestCredentials {
+            username: username.to_owned(),
+            realm: challenge.realm.clone(),
+            nonce: challenge.nonce.clone(),
+            uri: uri.to_owned(),
+            response: String::new(),
+            algorithm: challenge.algorithm.clone(),
+            cnonce: if uses_cnonce {
+                Some(cnonce.to_owned())
+            } else {
+                None
+            },
+            opaque: challenge.opaque.clone(),
+            nc: qop.as_ref().map(|_| nc),
+            qop,
+        }
+    }
+
+    fn from_params(params: &Params) -> Option<DigestCredentials> {
+        Some(DigestCredentials {
+            username: params.get("username")?.to_owned(),
+            realm: params.get("realm")?.to_owned(),
+            nonce: params.get("nonce")?.to_owned(),
+            uri: params.get("uri")?.to_owned(),
+            response: params.get("response")?.to_owned(),
+            algorithm: params.get("algorithm").map(Algorithm::from),
+            cnonce: params.get("cnonce").map(|c| c.to_owned()),
+            opaque: params.get("opaque").map(|o| o.to_owned()),
+            qop: params.get("qop").map(Qop::from),
+            nc: params
+                .get("nc")
+                .and_then(|nc| u32::from_str_radix(nc, 16).ok()),
+        })
+    }
+
+    fn algorithm(&self) -> &Algorithm {
+        self.algorithm.as_ref().unwrap_or(&Algorithm::Md5)
+    }
+
+    /// Computes the response from H(A1), as stored by registrars which don't keep plain
+    /// passwords. `None` for unknown algorithms.
+    pub fn compute_response_ha1(&self, ha1: &str, method: &Method, body: &[u8]) -> Option<String> {
+        self.digest(ha1, method.as_str(), body)
+    }
+
+    /// Computes the `rspauth` of `Authentication-Info`, which is the response with an empty
+    /// method (RFC 2617 section 3.2.3).
+    pub fn compute_rspauth_ha1(&self, ha1: &str, body: &[u8]) -> Option<String> {
+        self.digest(ha1, "", body)
+    }
+
+    fn digest(&self, ha1: &str, method: &str, body: &[u8]) -> Option<String> {
+        let algorithm = self.algorithm();
+        let cnonce = self.cnonce.as_deref().unwrap_or_default();
+
+        let ha1 = if algorithm.is_sess() {
+            algorithm.hash(format!("{}:{}:{}", ha1, self.nonce, cnonce).as_bytes())?
+        } else {
+            ha1.to_owned()
+        };
+
+        let ha2 = match self.qop {
+            Some(Qop::AuthInt) => {
+                let body = algorithm.hash(body)?;
+                algorithm.hash(format!("{}:{}:{}", method, self.uri, body).as_bytes())?
+            }
+            _ => algorithm.hash(format!("{}:{}", method, self.uri).as_bytes())?,
+        };
+
+        match &self.qop {
+            Some(qop) => algorithm.hash(
+                format!(
+                    "{}:{}:{:08x}:{}:{}:{}",
+                    ha1,
+                    self.nonce,
+                    self.nc.unwrap_or_default(),
+                    cnonce,
+                    qop,
+                    ha2
+                )
+                .as_bytes(),
+            ),
+            None => algorithm.hash(format!("{}:{}:{}", ha1, self.nonce, ha2).as_bytes()),
+        }
+    }
+
+    pub fn compute_response(&self, password: &str, method: &Method, body: &[u8]) -> Option<String> {
+        let ha1 = digest_ha1(self.algorithm(), &self.username, &self.realm, password)?;
+        self.compute_response_ha1(&ha1, method, body)
+    }
+
+    /// Fills the response, returning false when the algorithm is not supported.
+    pub fn sign(&mut self, password: &str, method: &Method, body: &[u8]) -> bool {
+        match self.compute_response(password, method, body) {
+            Some(response) => {
+                self.response = response;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks the response against the user password.
+    pub fn verify(&self, password: &str, method: &Method, body: &[u8]) -> bool {
+        self.compute_response(password, method, body)
+            .is_some_and(|r| constant_time_eq(&r, &self.response))
+    }
+
+    /// Checks the response against the stored H(A1) of the user.
+    pub fn verify_ha1(&self, ha1: &str, method: &Method, body: &[u8]) -> bool {
+        self.compute_response_ha1(ha1, method, body)
+            .is_some_and(|r| constant_time_eq(&r, &self.response))
+    }
+}
+
+/// Writes the credentials in wire format, including the `Digest` scheme.
+impl fmt::Display for DigestCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", response=\"{}\"",
+            escape_quoted(&self.username),
+            escape_quoted(&self.realm),
+            escape_quoted(&self.nonce),
+            escape_quoted(&self.uri),
+            self.response
+        )?;
+
+        if let Some(algorithm) = &self.algorithm {
+            write!(f, ", algorithm={}", algorithm)?;
+        }
+
+        if let Some(cnonce) = &self.cnonce {
+            write!(f, ", cnonce=\"{}\"", escape_quoted(cnonce))?;
+        }
+
+        if let Some(opaque) = &self.opaque {
+            write!(f, ", opaque=\"{}\"", escape_quoted(opaque))?;
+        }
+
+        if let Some(qop) = &self.qop {
+            write!(f, ", qop={}", qop)?;
+        }
+
+        if let Some(nc) = self.nc {
+            write!(f, ", nc={:08x}", nc)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Challenge of any auth scheme, as found on `WWW-Authenticate` and `Proxy-Authenticate`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Challenge {
+    Digest(DigestChallenge),
+    Other { scheme: String, params: Params },
+}
+
+impl Challenge {
+    pub fn scheme(&self) -> &str {
+        match self {
+            Challenge::Digest(_) => "Digest",
+            Challenge::Other { scheme, .. } => scheme,
+        }
+    }
+
+    pub fn digest(&self) -> Option<&DigestChallenge> {
+        match self {
+            Challenge::Digest(d) => Some(d),
+            Challenge::Other { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Challenge::Digest(d) => write!(f, "{}", d),
+            Challenge::Other { scheme, params } => {
+                write!(f, "{}", scheme)?;
+                write_auth_params(f, params)
+            }
+        }
+    }
+}
+
+/// Credentials of any auth scheme, as found on `Authorization` and `Proxy-Authorization`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Credentials {
+    Digest(DigestCredentials),
+    /// Token of the `Bearer` scheme (RFC 8898).
+    Bearer(String),
+    Other {
+        scheme: String,
+        params: Params,
+    },
+}
+
+impl Credentials {
+    pub fn scheme(&self) -> &str {
+        match self {
+            Credentials::Digest(_) => "Digest",
+            Credentials::Bearer(_) => "Bearer",
+            Credentials::Other { scheme, .. } => scheme,
+        }
+    }
+
+    pub fn digest(&self) -> Option<&DigestCredentials> {
+        match self {
+            Credentials::Digest(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn digest_mut(&mut self) -> Option<&mut DigestCredentials> {
+        match self {
+            Credentials::Digest(d) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Credentials::Digest(d) => write!(f, "{}", d),
+            Credentials::Bearer(token) => write!(f, "Bearer {}", token),
+            Credentials::Other { scheme, params } => {
+                write!(f, "{}", scheme)?;
+                write_auth_params(f, params)
+            }
+        }
+    }
+}
+
+/// `Authentication-Info` sent by the server after a successful digest authentication.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct AuthenticationInfo {
+    pub nextnonce: Option<String>,
+    pub qop: Option<Qop>,
+    pub rspauth: Option<String>,
+    pub cnonce: Option<String>,
+    pub nc: Option<u32>,
+}
+
+impl AuthenticationInfo {
+    /// Info answering `credentials`, with `rspauth` computed from the H(A1) of the user.
+    pub fn for_credentials(
+        credentials: &DigestCredentials,
+        ha1: &str,
+        body: &[u8],
+    ) -> AuthenticationInfo {
+        AuthenticationInfo {
+            nextnonce: None,
+            qop: credentials.qop.clone(),
+            rspauth: credentials.compute_rspauth_ha1(ha1, body),
+            cnonce: credentials.cnonce.clone(),
+            nc: credentials.nc,
+        }
+    }
+
+    fn from_params(params: &Params) -> AuthenticationInfo {
+        AuthenticationInfo {
+            nextnonce: params.get("nextnonce").map(|n| n.to_owned()),
+            qop: params.get("qop").map(Qop::from),
+            rspauth: params.get("rspauth").map(|r| r.to_owned()),
+            cnonce: params.get("cnonce").map(|c| c.to_owned()),
+            nc: params
+                .get("nc")
+                .and_then(|nc| u32::from_str_radix(nc, 16).ok()),
+        }
+    }
+}
+
+impl fmt::Display for AuthenticationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params = vec![];
+
+        if let Some(nextnonce) = &self.nextnonce {
+            params.push(format!("nextnonce=\"{}\"", escape_quoted(nextnonce)));
+        }
+
+        if let Some(qop) = &self.qop {
+            params.push(format!("qop={}", qop));
+        }
+
+        if let Some(rspauth) = &self.rspauth {
+            params.push(format!("rspauth=\"{}\"", escape_quoted(rspauth)));
+        }
+
+        if let Some(cnonce) = &self.cnonce {
+            params.push(format!("cnonce=\"{}\"", escape_quoted(cnonce)));
+        }
+
+        if let Some(nc) = self.nc {
+            params.push(format!("nc={:08x}", nc));
+        }
+
+        write!(f, "{}", params.join(", "))
+    }
+}
+
+//Writes auth params after the scheme, separated by commas instead of the usual `;`
+fn write_auth_params(f: &mut fmt::Formatter, params: &Params) -> fmt::Result {
+    let params = params.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+    if params.is_empty() {
+        Ok(())
+    } else {
+        write!(f, " {}", params.join(", "))
+    }
+}
+
+/// Characters of a token68, like Bearer tokens, besides the trailing `=` padding.
+fn is_token68_char(c: u8) -> bool {
+    nom::is_alphanumeric(c) || b"-._~+/".contains(&c)
+}
+
+//Compares hex digests ignoring case, without stopping at the first difference, so the time taken
+//doesn't tell how much of a forged response was right
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// H(A1) of a user, without the `-sess` part, which is what registrars usually store.
+pub fn digest_ha1(
+    algorithm: &Algorithm,
+    username: &str,
+    realm: &str,
+    password: &str,
+) -> Option<String> {
+    algorithm.hash(format!("{}:{}:{}", username, realm, password).as_bytes())
+}
+
+//Comma between auth params, which may be surrounded by whitespace
+named!(
+    parse_comma<&[u8]>,
+    delimited!(
+        take_while!(nom::is_space),
+        tag!(","),
+        take_while!(nom::is_space)
+    )
+);
+
+named!(
+    pub parse_auth_params<Params>,
+    do_parse!(
+        params: separated_list_complete!(parse_comma, parse_param)
+            >> (params.into_iter().collect())
+    )
+);
+
+named!(
+    parse_digest_params<Params>,
+    do_parse!(
+        take_while!(nom::is_space)
+            >> tag_no_case!("Digest")
+            >> take_while1!(nom::is_space)
+            >> params: parse_auth_params
+            >> (params)
+    )
+);
+
+named!(
+    pub parse_digest_challenge<DigestChallenge>,
+    map_opt!(parse_digest_params, |p: Params| DigestChallenge::from_params(&p))
+);
+
+named!(
+    pub parse_digest_credentials<DigestCredentials>,
+    map_opt!(parse_digest_params, |p: Params| DigestCredentials::from_params(&p))
+);
+
+named!(
+    parse_other_params<(String, Params)>,
+    do_parse!(
+        take_while!(nom::is_space)
+            >> scheme: map!(take_while1!(is_param_char), to_str_default)
+            >> take_while!(nom::is_space)
+            >> params: parse_auth_params
+            >> (scheme, params)
+    )
+);
+
+named!(
+    parse_bearer_token<String>,
+    do_parse!(
+        take_while!(nom::is_space)
+            >> tag_no_case!("Bearer")
+            >> take_while1!(nom::is_space)
+            >> token: recognize!(pair!(
+                take_while1!(is_token68_char),
+                take_while!(|c| c == b'=')
+            ))
+            >> (to_str_default(token))
+    )
+);
+
+//A malformed digest falls back to `Other`, so its params are kept
+named!(
+    pub parse_challenge<Challenge>,
+    alt_complete!(
+        map!(parse_digest_challenge, Challenge::Digest)
+            | map!(parse_other_params, |(scheme, params)| Challenge::Other {
+                scheme,
+                params
+            })
+    )
+);
+
+named!(
+    pub parse_credentials<Credentials>,
+    alt_complete!(
+        map!(parse_digest_credentials, Credentials::Digest)
+            | map!(parse_bearer_token, Credentials::Bearer)
+            | map!(parse_other_params, |(scheme, params)| Credentials::Other {
+                scheme,
+                params
+            })
+    )
+);
+
+named!(
+    pub parse_authentication_info<AuthenticationInfo>,
+    map!(
+        preceded!(take_while!(nom::is_space), parse_auth_params),
+        |p: Params| AuthenticationInfo::from_params(&p)
+    )
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge_parse() {
+        let (_, challenge) = parse_digest_challenge(
+            b"Digest realm=\"atlanta.com, biloxi.com\", domain=\"sip:ss1.carrier.com\", qop=\"auth,auth-int\", nonce=\"f84f1cec41e6cbe5aea9c8e88d359\", opaque=\"\", stale=FALSE, algorithm=MD5\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(challenge.realm, "atlanta.com, biloxi.com");
+        assert_eq!(challenge.domain, Some("sip:ss1.carrier.com".to_owned()));
+        assert_eq!(challenge.nonce, "f84f1cec41e6cbe5aea9c8e88d359");
+        assert_eq!(challenge.opaque, Some("".to_owned()));
+        assert!(!challenge.stale);
+        assert_eq!(challenge.algorithm, Some(Algorithm::Md5));
+        assert_eq!(challenge.qop, vec![Qop::Auth, Qop::AuthInt]);
+        assert_eq!(
+            challenge.to_string(),
+            "Digest realm=\"atlanta.com, biloxi.com\", domain=\"sip:ss1.carrier.com\", nonce=\"f84f1cec41e6cbe5aea9c8e88d359\", opaque=\"\", algorithm=MD5, qop=\"auth,auth-int\""
+        );
+
+        assert!(parse_digest_challenge(b"Digest realm=\"a\"\r\n").is_err());
+    }
+
+    #[test]
+    fn credentials_parse() {
+        let data: &[u8] = b"Digest username=\"bob\", realm=\"biloxi.com\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"sip:bob@biloxi.com\", qop=auth, nc=0000000a, cnonce=\"0a4f113b\", response=\"6629fae49393a05397450978507c4ef1\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"\r\n";
+        let (_, credentials) = parse_digest_credentials(data).unwrap();
+
+        assert_eq!(credentials.username, "bob");
+        assert_eq!(credentials.uri, "sip:bob@biloxi.com");
+        assert_eq!(credentials.qop, Some(Qop::Auth));
+        assert_eq!(credentials.nc, Some(10));
+        assert_eq!(credentials.cnonce, Some("0a4f113b".to_owned()));
+        assert_eq!(credentials.algorithm, None);
+        assert_eq!(
+            credentials.to_string(),
+            "Digest username=\"bob\", realm=\"biloxi.com\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"sip:bob@biloxi.com\", response=\"6629fae49393a05397450978507c4ef1\", cnonce=\"0a4f113b\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\", qop=auth, nc=0000000a"
+        );
+    }
+
+    //Examples from RFC 2617 section 3.5 and RFC 7616 section 3.9.1
+    #[test]
+    fn response_known_values() {
+        let get = Method::Unknown("GET".to_owned());
+        let mut credentials = DigestCredentials {
+            username: "Mufasa".to_owned(),
+            realm: "testrealm@host.com".to_owned(),
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093".to_owned(),
+            uri: "/dir/index.html".to_owned(),
+            cnonce: Some("0a4f113b".to_owned()),
+            qop: Some(Qop::Auth),
+            nc: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            credentials.compute_response("Circle Of Life", &get, b""),
+            Some("6629fae49393a05397450978507c4ef1".to_owned())
+        );
+
+        credentials.realm = "http-auth@example.org".to_owned();
+        credentials.nonce = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v".to_owned();
+        credentials.cnonce = Some("f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ".to_owned());
+        assert_eq!(
+            credentials.compute_response("Circle of Life", &get, b""),
+            Some("8ca523f5e9506fed4657c9700eebdbec".to_owned())
+        );
+
+        credentials.algorithm = Some(Algorithm::Sha256);
+        assert_eq!(
+            credentials.compute_response("Circle of Life", &get, b""),
+            Some("753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1".to_owned())
+        );
+
+        assert_eq!(
+            Algorithm::Sha512_256.hash(b"abc"),
+            Some("53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23".to_owned())
+        );
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let algorithms = [
+            Algorithm::Md5,
+            Algorithm::Md5Sess,
+            Algorithm::Sha256,
+            Algorithm::Sha256Sess,
+            Algorithm::Sha512_256,
+            Algorithm::Sha512_256Sess,
+        ];
+
+        for algorithm in algorithms.iter() {
+            let challenge = DigestChallenge {
+                realm: "biloxi.com".to_owned(),
+                nonce: "ea9c8e88df84f1cec4341ae6cbe5a359".to_owned(),
+                algorithm: Some(algorithm.clone()),
+                qop: vec![Qop::AuthInt],
+                ..Default::default()
+            };
+
+            let mut credentials =
+                DigestCredentials::for_challenge(&challenge, "bob", "sip:bob@biloxi.com", "c1", 1);
+            assert!(credentials.sign("zanzibar", &Method::Invite, b"v=0\r\n"));
+
+            assert!(credentials.verify("zanzibar", &Method::Invite, b"v=0\r\n"));
+            assert!(!credentials.verify("zanzibar", &Method::Invite, b"v=1\r\n"));
+            assert!(!credentials.verify("zanzibar", &Method::Register, b"v=0\r\n"));
+            assert!(!credentials.verify("wrong", &Method::Invite, b"v=0\r\n"));
+
+            let ha1 = digest_ha1(algorithm, "bob", "biloxi.com", "zanzibar").unwrap();
+            assert!(credentials.verify_ha1(&ha1, &Method::Invite, b"v=0\r\n"));
+
+            credentials.response = credentials.response.to_ascii_uppercase();
+            assert!(credentials.verify_ha1(&ha1, &Method::Invite, b"v=0\r\n"));
+
+            credentials.response.pop();
+            assert!(!credentials.verify_ha1(&ha1, &Method::Invite, b"v=0\r\n"));
+        }
+
+        let mut credentials = DigestCredentials {
+            algorithm: Some(Algorithm::Unknown("AKAv1-MD5".to_owned())),
+            ..Default::default()
+        };
+        assert!(!credentials.sign("zanzibar", &Method::Register, b""));
+    }
+
+    #[test]
+    fn credentials_without_qop() {
+        let challenge = DigestChallenge {
+            realm: "biloxi.com".to_owned(),
+            nonce: "abc".to_owned(),
+            ..Default::default()
+        };
+
+        let mut credentials =
+            DigestCredentials::for_challenge(&challenge, "bob", "sip:biloxi.com", "c1", 1);
+        assert!(credentials.sign("zanzibar", &Method::Register, b""));
+
+        assert_eq!(credentials.cnonce, None);
+        assert_eq!(credentials.nc, None);
+        assert!(credentials.to_string().ends_with("\""));
+        assert!(credentials.verify("zanzibar", &Method::Register, b""));
+    }
+
+    #[test]
+    fn other_schemes() {
+        let (_, bearer) = parse_credentials(b"Bearer eyJhbGciOi.JSUzI1-NiJ9_==\r\n").unwrap();
+        assert_eq!(
+            bearer,
+            Credentials::Bearer("eyJhbGciOi.JSUzI1-NiJ9_==".to_owned())
+        );
+        assert_eq!(bearer.to_string(), "Bearer eyJhbGciOi.JSUzI1-NiJ9_==");
+        assert_eq!(bearer.digest(), None);
+
+        let (_, challenge) = parse_challenge(
+            b"Bearer realm=\"atlanta.com\", scope=\"abc\", authz_server=\"https://as.example.com/token\"\r\n",
+        )
+        .unwrap();
+        assert_eq!(challenge.scheme(), "Bearer");
+        assert_eq!(
+            challenge.to_string(),
+            "Bearer realm=\"atlanta.com\", scope=\"abc\", authz_server=\"https://as.example.com/token\""
+        );
+
+        //Digest without a nonce is kept as is
+        let (_, challenge) = parse_challenge(b"Digest realm=\"a\"\r\n").unwrap();
+        assert_eq!(challenge.digest(), None);
+        assert_eq!(challenge.to_string(), "Digest realm=\"a\"");
+
+        let (_, credentials) = parse_credentials(
+            b"Digest username=\"bob\", realm=\"biloxi.com\", nonce=\"a\", uri=\"sip:biloxi.com\", response=\"b\"\r\n",
+        )
+        .unwrap();
+        assert_eq!(credentials.scheme(), "Digest");
+        assert_eq!(
+            credentials.digest().map(|d| d.username.as_str()),
+            Some("bob")
+        );
+    }
+
+    #[test]
+    fn authentication_info() {
+        let (_, info) = parse_authentication_info(
+            b"nextnonce=\"47364c23432d2e131a5fb210812c\", qop=auth, rspauth=\"8ca5\", cnonce=\"0a4f113b\", nc=00000001\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            info.nextnonce,
+            Some("47364c23432d2e131a5fb210812c".to_owned())
+        );
+        assert_eq!(info.qop, Some(Qop::Auth));
+        assert_eq!(info.nc, Some(1));
+        assert_eq!(
+            info.to_string(),
+            "nextnonce=\"47364c23432d2e131a5fb210812c\", qop=auth, rspauth=\"8ca5\", cnonce=\"0a4f113b\", nc=00000001"
+        );
+
+        let challenge = DigestChallenge {
+            realm: "biloxi.com".to_owned(),
+            nonce: "abc".to_owned(),
+            qop: vec![Qop::Auth],
+            ..Default::default()
+        };
+        let mut credentials =
+            DigestCredentials::for_challenge(&challenge, "bob", "sip:biloxi.com", "c1", 1);
+        assert!(credentials.sign("zanzibar", &Method::Register, b""));
+
+        let ha1 = digest_ha1(&Algorithm::Md5, "bob", "biloxi.com", "zanzibar").unwrap();
+        let info = AuthenticationInfo::for_credentials(&credentials, &ha1, b"");
+        let ha2 = Algorithm::Md5.hash(b":sip:biloxi.com").unwrap();
+        let rspauth =
+            Algorithm::Md5.hash(format!("{}:abc:00000001:c1:auth:{}", ha1, ha2).as_bytes());
+
+        assert_eq!(info.rspauth, rspauth);
+        assert_ne!(info.rspauth, Some(credentials.response));
+        assert_eq!(info.cnonce, Some("c1".to_owned()));
+    }
+}
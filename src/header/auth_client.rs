@@ -0,0 +1,412 @@
+use super::*;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//Last challenge received for a realm, with the nonce count already used with its nonce
+#[derive(Debug, Clone)]
+struct CachedChallenge {
+    challenge: DigestChallenge,
+    proxy: bool,
+    nc: u32,
+}
+
+/// Answers digest challenges on behalf of a user, as a UAC does after a `401` or `407`.
+///
+/// Challenges are cached by realm, so later requests can be authorized before being sent, with
+/// the nonce count increasing each time the same nonce is used.
+#[derive(Clone)]
+pub struct DigestClient {
+    username: String,
+    password: String,
+    challenges: Vec<CachedChallenge>,
+}
+
+impl DigestClient {
+    pub fn new(username: &str, password: &str) -> DigestClient {
+        DigestClient {
+            username: username.to_owned(),
+            password: password.to_owned(),
+            challenges: vec![],
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Builds the request to be re-sent after `response` challenged `request`. The new request
+    /// keeps `Call-ID` and the `From` tag, increments the `CSeq`, gets a new branch on the top
+    /// `Via` and carries the credentials of every digest challenge of `response`.
+    ///
+    /// Returns `None` when `response` isn't a `401` or `407` with a supported challenge, or when
+    /// `request` already had credentials for a challenged realm and the challenge isn't stale.
+    /// Those credentials were rejected, so the password is wrong and retrying would loop, even if
+    /// the server sends a new nonce each time.
+    pub fn authenticate(
+        &mut self,
+        request: &SipMessage,
+        response: &SipMessage,
+    ) -> Option<SipMessage> {
+        let proxy = match response.method {
+            SipMethod::Response { code: 401, .. } => false,
+            SipMethod::Response { code: 407, .. } => true,
+            _ => return None,
+        };
+        let (challenge_name, credentials_name) = auth_header_names(proxy);
+
+        let challenges = response
+            .headers
+            .get_all(challenge_name)
+            .filter_map(|h| match h {
+                SipHeader::WWWAuthenticate(c) | SipHeader::ProxyAuthenticate(c) => c.digest(),
+                _ => None,
+            })
+            .filter(|c| is_supported(c))
+            .collect::<Vec<_>>();
+
+        if challenges.is_empty() {
+            return None;
+        }
+
+        //Every challenge is checked before caching any, so a rejection leaves none of them cached
+        let rejected = challenges.iter().any(|challenge| {
+            !challenge.stale
+                && request
+                    .headers
+                    .get_all(credentials_name)
+                    .any(|h| credentials_of(h).is_some_and(|c| c.realm == challenge.realm))
+        });
+
+        if rejected {
+            return None;
+        }
+
+        for challenge in challenges {
+            self.cache(challenge, proxy);
+        }
+
+        let mut request = request.clone();
+
+        if let Some(SipHeader::CSeq { seq, .. }) = request.headers.first_mut("CSeq") {
+            *seq += 1;
+        }
+
+        if let Some(SipHeader::Via(via)) = request.headers.first_mut("Via") {
+            via.set_branch(&format!("{}{}", MAGIC_COOKIE, random_token()));
+        }
+
+        if self.authorize(&mut request) {
+            Some(request)
+        } else {
+            None
+        }
+    }
+
+    /// Adds credentials for every cached challenge to `request`, replacing the ones it already
+    /// had for the same realms. Returns false when nothing was cached or the request has no
+    /// method to sign.
+    pub fn authorize(&mut self, request: &mut SipMessage) -> bool {
+        let (method, uri) = match &request.method {
+            SipMethod::Request { method, uri, .. } => (method.clone(), uri.to_string()),
+            SipMethod::Response { .. } => return false,
+        };
+
+        if self.challenges.is_empty() {
+            return false;
+        }
+
+        for cached in self.challenges.iter_mut() {
+            cached.nc += 1;
+
+            let mut credentials = DigestCredentials::for_challenge(
+                &cached.challenge,
+                &self.username,
+                &uri,
+                &random_token(),
+                cached.nc,
+            );
+
+            if !credentials.sign(&self.password, &method, &request.body) {
+                continue;
+            }
+
+            let (_, name) = auth_header_names(cached.proxy);
+            replace_credentials(&mut request.headers, name, credentials, cached.proxy);
+        }
+
+        true
+    }
+
+    //Keeps the challenge of each realm, resetting the nonce count when the nonce changes
+    fn cache(&mut self, challenge: &DigestChallenge, proxy: bool) {
+        match self
+            .challenges
+            .iter_mut()
+            .find(|c| c.challenge.realm == challenge.realm && c.proxy == proxy)
+        {
+            Some(cached) => {
+                if cached.challenge.nonce != challenge.nonce {
+                    cached.nc = 0;
+                }
+                cached.challenge = challenge.clone();
+            }
+            None => self.challenges.push(CachedChallenge {
+                challenge: challenge.clone(),
+                proxy,
+                nc: 0,
+            }),
+        }
+    }
+}
+
+/// Never prints the password.
+impl fmt::Debug for DigestClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DigestClient {{ username: {:?}, password: \"<redacted>\", challenges: {:?} }}",
+            self.username, self.challenges
+        )
+    }
+}
+
+//Names of the challenge and credentials headers, for either a 401 or a 407
+fn auth_header_names(proxy: bool) -> (&'static str, &'static str) {
+    if proxy {
+        ("Proxy-Authenticate", "Proxy-Authorization")
+    } else {
+        ("WWW-Authenticate", "Authorization")
+    }
+}
+
+fn credentials_of(header: &SipHeader) -> Option<&DigestCredentials> {
+    match header {
+        SipHeader::Authorization(c) | SipHeader::ProxyAuthorization(c) => c.digest(),
+        _ => None,
+    }
+}
+
+fn is_supported(challenge: &DigestChallenge) -> bool {
+    challenge
+        .algorithm
+        .as_ref()
+        .is_none_or(|a| a.hash(b"").is_some())
+}
+
+//Replaces the credentials of the same realm where they are, or appends them when there are none,
+//so the other headers keep their order
+fn replace_credentials(
+    headers: &mut SipHeaders,
+    name: &str,
+    credentials: DigestCredentials,
+    proxy: bool,
+) {
+    let realm = credentials.realm.clone();
+    let credentials = Credentials::Digest(credentials);
+    let header = if proxy {
+        SipHeader::ProxyAuthorization(credentials)
+    } else {
+        SipHeader::Authorization(credentials)
+    };
+
+    match headers.find_mut(name, |h| {
+        credentials_of(h).is_some_and(|c| c.realm == realm)
+    }) {
+        Some(existing) => *existing = header,
+        None => headers.push(name.to_owned(), header),
+    }
+}
+
+//Token for cnonces and branches, hashing the clock with the randomly keyed std hasher to avoid
+//pulling a random number crate. It's unique enough for those, but it isn't a CSPRNG
+fn random_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    hasher.write_u128(nanos);
+
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTER: &[u8] = b"REGISTER sip:registrar.biloxi.com SIP/2.0\r\n\
+        Via: SIP/2.0/UDP bobspc.biloxi.com:5060;branch=z9hG4bKnashds7\r\n\
+        Max-Forwards: 70\r\n\
+        To: Bob <sip:bob@biloxi.com>\r\n\
+        From: Bob <sip:bob@biloxi.com>;tag=456248\r\n\
+        Call-ID: 843817637684230@998sdasdh09\r\n\
+        CSeq: 1826 REGISTER\r\n\
+        Contact: <sip:bob@192.0.2.4>\r\n\
+        Content-Length: 0\r\n\
+        \r\n";
+
+    fn message(data: &[u8]) -> SipMessage {
+        MessageParser::new(data).next_message().unwrap()
+    }
+
+    fn challenge_response(code: u32, name: &str, nonce: &str, stale: bool) -> SipMessage {
+        let data = format!(
+            "SIP/2.0 {} Unauthorized\r\n\
+             Via: SIP/2.0/UDP bobspc.biloxi.com:5060;branch=z9hG4bKnashds7\r\n\
+             To: Bob <sip:bob@biloxi.com>;tag=2493k59kd\r\n\
+             From: Bob <sip:bob@biloxi.com>;tag=456248\r\n\
+             Call-ID: 843817637684230@998sdasdh09\r\n\
+             CSeq: 1826 REGISTER\r\n\
+             {}: Digest realm=\"biloxi.com\", qop=\"auth\", nonce=\"{}\", stale={}\r\n\
+             Content-Length: 0\r\n\
+             \r\n",
+            code, name, nonce, stale
+        );
+        message(data.as_bytes())
+    }
+
+    fn credentials(msg: &SipMessage, name: &str) -> DigestCredentials {
+        credentials_of(msg.headers.first(name).unwrap())
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn retry_after_401() {
+        let request = message(REGISTER);
+        let response = challenge_response(401, "WWW-Authenticate", "ea9c8e88df84f1cec4", false);
+        let mut client = DigestClient::new("bob", "zanzibar");
+
+        let retry = client.authenticate(&request, &response).unwrap();
+
+        assert_eq!(
+            retry.headers.first("CSeq"),
+            Some(&SipHeader::CSeq {
+                seq: 1827,
+                method: Method::Register
+            })
+        );
+        assert_eq!(
+            retry.headers.first("Call-ID"),
+            request.headers.first("Call-ID")
+        );
+        assert_eq!(retry.headers.first("From"), request.headers.first("From"));
+        assert!(!retry.headers.contains("Proxy-Authorization"));
+
+        match (retry.headers.first("Via"), request.headers.first("Via")) {
+            (Some(SipHeader::Via(new)), Some(SipHeader::Via(old))) => {
+                assert!(new.has_magic_cookie());
+                assert_ne!(new.branch(), old.branch());
+            }
+            _ => panic!("Missing Via"),
+        }
+
+        let auth = credentials(&retry, "Authorization");
+        assert_eq!(auth.username, "bob");
+        assert_eq!(auth.uri, "sip:registrar.biloxi.com");
+        assert_eq!(auth.nc, Some(1));
+        assert!(auth.verify("zanzibar", &Method::Register, b""));
+
+        //Same credentials rejected again with the same nonce
+        assert!(client.authenticate(&retry, &response).is_none());
+
+        //Stale nonce, so the retry is made with the new one
+        let stale = challenge_response(401, "WWW-Authenticate", "a7a2b19c63dd", true);
+        let retry = client.authenticate(&retry, &stale).unwrap();
+        let auth = credentials(&retry, "Authorization");
+
+        assert_eq!(retry.headers.get_all("Authorization").count(), 1);
+        assert_eq!(auth.nonce, "a7a2b19c63dd");
+        assert_eq!(auth.nc, Some(1));
+    }
+
+    #[test]
+    fn wrong_password_with_fresh_nonces() {
+        let request = message(REGISTER);
+        let mut client = DigestClient::new("bob", "wrong");
+
+        let first = challenge_response(401, "WWW-Authenticate", "4a1d6f0c", false);
+        let retry = client.authenticate(&request, &first).unwrap();
+
+        //The registrar sends a new nonce on every 401, which must not restart the retries
+        let second = challenge_response(401, "WWW-Authenticate", "9b3e2a7d", false);
+        assert!(client.authenticate(&retry, &second).is_none());
+
+        assert!(!format!("{:?}", client).contains("wrong"));
+    }
+
+    #[test]
+    fn rejected_with_two_realms() {
+        let mut client = DigestClient::new("bob", "zanzibar");
+        let first = challenge_response(401, "WWW-Authenticate", "4a1d6f0c", false);
+        let retry = client.authenticate(&message(REGISTER), &first).unwrap();
+
+        //A new realm comes first, but biloxi.com rejected the credentials already sent
+        let data = String::from_utf8(first.to_bytes()).unwrap().replace(
+            "WWW-Authenticate: Digest realm=\"biloxi.com\"",
+            "WWW-Authenticate: Digest realm=\"atlanta.com\", nonce=\"84f1c1ae6cbe\"\r\n\
+             WWW-Authenticate: Digest realm=\"biloxi.com\"",
+        );
+        let response = message(data.as_bytes());
+        assert_eq!(response.headers.get_all("WWW-Authenticate").count(), 2);
+
+        let mut client = DigestClient::new("bob", "zanzibar");
+        assert!(client.authenticate(&retry, &response).is_none());
+        assert!(!client.authorize(&mut message(REGISTER)));
+    }
+
+    #[test]
+    fn credentials_replaced_in_place() {
+        let data = String::from_utf8(REGISTER.to_vec()).unwrap().replace(
+            "Max-Forwards: 70\r\n",
+            "Authorization: Digest username=\"bob\", realm=\"biloxi.com\", nonce=\"a\", \
+             uri=\"sip:registrar.biloxi.com\", response=\"b\"\r\n\
+             Max-Forwards: 70\r\n\
+             Authorization: Digest username=\"bob\", realm=\"atlanta.com\", nonce=\"c\", \
+             uri=\"sip:registrar.biloxi.com\", response=\"d\"\r\n",
+        );
+        let request = message(data.as_bytes());
+        let stale = challenge_response(401, "WWW-Authenticate", "a7a2b19c63dd", true);
+        let mut client = DigestClient::new("bob", "zanzibar");
+
+        let retry = client.authenticate(&request, &stale).unwrap();
+        let names = |msg: &SipMessage| {
+            msg.headers
+                .iter()
+                .map(|e| e.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let realms = retry
+            .headers
+            .get_all("Authorization")
+            .map(|h| credentials_of(h).unwrap().realm.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names(&retry), names(&request));
+        assert_eq!(realms, vec!["biloxi.com", "atlanta.com"]);
+        assert_eq!(credentials(&retry, "Authorization").nonce, "a7a2b19c63dd");
+    }
+
+    #[test]
+    fn retry_after_407_and_nonce_count() {
+        let request = message(REGISTER);
+        let response = challenge_response(407, "Proxy-Authenticate", "dcd98b7102dd2f0e", false);
+        let mut client = DigestClient::new("bob", "zanzibar");
+
+        let retry = client.authenticate(&request, &response).unwrap();
+        assert!(!retry.headers.contains("Authorization"));
+        assert_eq!(credentials(&retry, "Proxy-Authorization").nc, Some(1));
+
+        //Next request reuses the cached nonce
+        let mut next = message(REGISTER);
+        assert!(client.authorize(&mut next));
+
+        let auth = credentials(&next, "Proxy-Authorization");
+        assert_eq!(auth.nc, Some(2));
+        assert!(auth.verify("zanzibar", &Method::Register, b""));
+
+        assert!(client.authenticate(&request, &message(REGISTER)).is_none());
+    }
+}
@@ -0,0 +1,443 @@
+use super::*;
+use std::fmt;
+use std::io::{self, Write};
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+
+//Original text of a header, kept when raw headers are preserved
+#[derive(PartialEq, Debug, Clone)]
+enum RawText {
+    None,
+    Line(Vec<u8>),
+    //The header was one of the values of a comma-separated line, kept by a previous entry
+    SameLine,
+}
+
+/// A single header value, as stored by `SipHeaders`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct HeaderEntry {
+    name: String,
+    header: SipHeader,
+    raw: RawText,
+}
+
+impl HeaderEntry {
+    /// Header name, spelled as it was received or added.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn header(&self) -> &SipHeader {
+        &self.header
+    }
+
+    /// Original line (without CRLF) this header was parsed from. Only available when raw headers
+    /// are preserved and the header was never modified. When several values were received on the
+    /// same line, only the first of them holds it.
+    pub fn raw(&self) -> Option<&[u8]> {
+        match &self.raw {
+            RawText::Line(raw) => Some(raw),
+            _ => None,
+        }
+    }
+
+    /// Writes the header line, without CRLF, preferring the original bytes when available.
+    pub fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match &self.raw {
+            RawText::Line(raw) => w.write_all(raw),
+            _ => write!(w, "{}: {}", self.name, self.header),
+        }
+    }
+
+    /// Same as `encode`, but using the compact form of the name when the header has one.
+    pub fn encode_compact<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match (&self.raw, self.header.compact_name()) {
+            (RawText::Line(_), _) | (_, None) => self.encode(w),
+            (_, Some(compact)) => write!(w, "{}: {}", compact, self.header),
+        }
+    }
+
+    /// Whether this entry is stored under `name`, which may be given in the full or compact form
+    /// and in any case.
+    pub fn is(&self, name: &str) -> bool {
+        is_same_header_name(self.header.name(), name)
+    }
+}
+
+impl fmt::Display for HeaderEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.raw {
+            RawText::Line(raw) => write!(f, "{}", String::from_utf8_lossy(raw)),
+            _ => write!(f, "{}: {}", self.name, self.header),
+        }
+    }
+}
+
+/// Ordered, multi-valued collection of parsed headers.
+///
+/// Headers are kept in the order they appeared on the wire and repeated headers (like several
+/// `Via` or `Contact` lines) are all preserved. Lookups are case-insensitive and accept both full
+/// and compact names, so `get_all("Via")` also returns headers received as `v` or `VIA`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SipHeaders {
+    entries: Vec<HeaderEntry>,
+}
+
+impl SipHeaders {
+    pub fn new() -> SipHeaders {
+        SipHeaders { entries: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Appends a header at the end, after any existing one with the same name.
+    pub fn push(&mut self, name: String, header: SipHeader) {
+        self.entries.push(HeaderEntry {
+            name,
+            header,
+            raw: RawText::None,
+        });
+    }
+
+    /// Inserts a header at `index`, before the one which was there. Panics when `index` is greater
+    /// than the number of headers.
+    pub fn insert(&mut self, index: usize, name: String, header: SipHeader) {
+        self.entries.insert(
+            index,
+            HeaderEntry {
+                name,
+                header,
+                raw: RawText::None,
+            },
+        );
+    }
+
+    /// Appends a header together with the original line it was parsed from, which is written
+    /// back as is by the encoder until the header is modified.
+    pub fn push_raw(&mut self, name: String, header: SipHeader, raw: Vec<u8>) {
+        self.entries.push(HeaderEntry {
+            name,
+            header,
+            raw: RawText::Line(raw),
+        });
+    }
+
+    /// Appends a header which was received on the same line as the previous one (as in
+    /// `Via: a, b`), whose original text was given to `push_raw`.
+    pub fn push_same_line(&mut self, name: String, header: SipHeader) {
+        let raw = match self.entries.last() {
+            Some(HeaderEntry {
+                raw: RawText::Line(_),
+                ..
+            })
+            | Some(HeaderEntry {
+                raw: RawText::SameLine,
+                ..
+            }) => RawText::SameLine,
+            _ => RawText::None,
+        };
+
+        self.entries.push(HeaderEntry { name, header, raw });
+    }
+
+    //Drops the original text of the entry at `pos` and of every other value on the same line
+    fn drop_raw(&mut self, pos: usize) {
+        let mut start = pos;
+        while start > 0 && self.entries[start].raw == RawText::SameLine {
+            start -= 1;
+        }
+
+        self.entries[start].raw = RawText::None;
+
+        for e in self.entries[start + 1..].iter_mut() {
+            if e.raw != RawText::SameLine {
+                break;
+            }
+            e.raw = RawText::None;
+        }
+    }
+
+    /// Replaces every header with the same name as `header` by it, keeping the position of the
+    /// first one. The header is appended when there was none.
+    pub fn set(&mut self, header: SipHeader) {
+        let name = header.name().to_owned();
+
+        match self.entries.iter().position(|e| e.is(&name)) {
+            Some(pos) => {
+                self.drop_raw(pos);
+                self.entries[pos].header = header;
+
+                let mut i = 0;
+                self.entries.retain(|e| {
+                    i += 1;
+                    i - 1 == pos || !e.is(&name)
+                });
+            }
+            None => self.push(name, header),
+        }
+    }
+
+    /// All headers with the given name, in the order they were received.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SipHeader> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| e.is(name))
+            .map(|e| &e.header)
+    }
+
+    pub fn first(&self, name: &str) -> Option<&SipHeader> {
+        self.entries.iter().find(|e| e.is(name)).map(|e| &e.header)
+    }
+
+    /// Mutable access to the first header with the given name. The header is considered modified,
+    /// so its original text is dropped and it is encoded from its parsed value.
+    pub fn first_mut(&mut self, name: &str) -> Option<&mut SipHeader> {
+        let pos = self.entries.iter().position(|e| e.is(name))?;
+        self.drop_raw(pos);
+        Some(&mut self.entries[pos].header)
+    }
+
+    /// Mutable access to the first header with the given name for which `predicate` is true. As
+    /// on `first_mut`, the header found is considered modified.
+    pub fn find_mut<P>(&mut self, name: &str, predicate: P) -> Option<&mut SipHeader>
+    where
+        P: Fn(&SipHeader) -> bool,
+    {
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.is(name) && predicate(&e.header))?;
+        self.drop_raw(pos);
+        Some(&mut self.entries[pos].header)
+    }
+
+    pub fn last(&self, name: &str) -> Option<&SipHeader> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.is(name))
+            .map(|e| &e.header)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.first(name).is_some()
+    }
+
+    /// Removes every header with the given name, returning how many were removed.
+    pub fn remove_all(&mut self, name: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|e| !e.is(name));
+        before - self.entries.len()
+    }
+
+    /// Writes every header line, each one followed by CRLF. Values which were received on a
+    /// single line are written back on that line while unmodified.
+    pub fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.encode_with(w, false)
+    }
+
+    /// Same as `encode`, but using the compact form of the names which have one.
+    pub fn encode_compact<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.encode_with(w, true)
+    }
+
+    fn encode_with<W: Write>(&self, w: &mut W, compact: bool) -> io::Result<()> {
+        for entry in self.entries.iter().filter(|e| e.raw != RawText::SameLine) {
+            if compact {
+                entry.encode_compact(w)?;
+            } else {
+                entry.encode(w)?;
+            }
+            w.write_all(b"\r\n")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, HeaderEntry> {
+        self.entries.iter()
+    }
+}
+
+impl fmt::Display for SipHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in self.entries.iter().filter(|e| e.raw != RawText::SameLine) {
+            write!(f, "{}\r\n", entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromIterator<(String, SipHeader)> for SipHeaders {
+    fn from_iter<I: IntoIterator<Item = (String, SipHeader)>>(iter: I) -> Self {
+        let mut headers = SipHeaders::new();
+
+        for (name, header) in iter {
+            headers.push(name, header);
+        }
+
+        headers
+    }
+}
+
+impl IntoIterator for SipHeaders {
+    type Item = HeaderEntry;
+    type IntoIter = vec::IntoIter<HeaderEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SipHeaders {
+    type Item = &'a HeaderEntry;
+    type IntoIter = slice::Iter<'a, HeaderEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported(s: &str) -> SipHeader {
+        SipHeader::Supported(vec![s.to_owned()])
+    }
+
+    fn sample() -> SipHeaders {
+        vec![
+            ("Supported".to_owned(), supported("first")),
+            ("Max-Forwards".to_owned(), SipHeader::MaxForwards(70)),
+            ("Supported".to_owned(), supported("second")),
+            ("Supported".to_owned(), supported("third")),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn keeps_duplicates_in_order() {
+        let headers = sample();
+
+        assert_eq!(headers.len(), 4);
+        assert_eq!(
+            headers.get_all("Supported").collect::<Vec<_>>(),
+            vec![
+                &supported("first"),
+                &supported("second"),
+                &supported("third"),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_and_last() {
+        let headers = sample();
+
+        assert_eq!(headers.first("Supported"), Some(&supported("first")));
+        assert_eq!(headers.last("Supported"), Some(&supported("third")));
+        assert_eq!(headers.first("Contact"), None);
+    }
+
+    #[test]
+    fn remove_all() {
+        let mut headers = sample();
+
+        assert_eq!(headers.remove_all("Supported"), 3);
+        assert!(!headers.contains("Supported"));
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn set_replaces_all() {
+        let mut headers = sample();
+        headers.push("Call-ID".to_owned(), SipHeader::CallID("a".to_owned()));
+        headers.push("Call-ID".to_owned(), SipHeader::CallID("b".to_owned()));
+
+        headers.set(SipHeader::CallID("c".to_owned()));
+        assert_eq!(
+            headers.get_all("Call-ID").collect::<Vec<_>>(),
+            vec![&SipHeader::CallID("c".to_owned())]
+        );
+        assert_eq!(headers.len(), 5);
+
+        headers.set(SipHeader::Expires(60));
+        assert_eq!(headers.last("Expires"), Some(&SipHeader::Expires(60)));
+    }
+
+    #[test]
+    fn find_mut() {
+        let mut headers = sample();
+
+        if let Some(SipHeader::Supported(list)) =
+            headers.find_mut("Supported", |h| h == &supported("second"))
+        {
+            list[0] = "changed".to_owned();
+        }
+
+        assert_eq!(
+            headers.get_all("Supported").collect::<Vec<_>>(),
+            vec![
+                &supported("first"),
+                &supported("changed"),
+                &supported("third"),
+            ]
+        );
+        assert_eq!(headers.find_mut("Supported", |_| false), None);
+    }
+
+    #[test]
+    fn raw_dropped_on_modification() {
+        let mut headers = SipHeaders::new();
+        headers.push_raw(
+            "Max-Forwards".to_owned(),
+            SipHeader::MaxForwards(70),
+            b"Max-Forwards:   70".to_vec(),
+        );
+
+        let entry = headers.iter().next().unwrap();
+        assert_eq!(entry.to_string(), "Max-Forwards:   70");
+
+        if let Some(SipHeader::MaxForwards(n)) = headers.first_mut("Max-Forwards") {
+            *n = 69;
+        }
+
+        let entry = headers.iter().next().unwrap();
+        assert_eq!(entry.raw(), None);
+        assert_eq!(entry.to_string(), "Max-Forwards: 69");
+    }
+
+    #[test]
+    fn raw_same_line() {
+        let mut headers = SipHeaders::new();
+        headers.push_raw(
+            "Supported".to_owned(),
+            supported("first"),
+            b"Supported:first,second".to_vec(),
+        );
+        headers.push_same_line("Supported".to_owned(), supported("second"));
+        headers.push("Max-Forwards".to_owned(), SipHeader::MaxForwards(70));
+
+        assert_eq!(
+            headers.to_string(),
+            "Supported:first,second\r\nMax-Forwards: 70\r\n"
+        );
+
+        headers.first_mut("Supported");
+
+        assert_eq!(
+            headers.to_string(),
+            "Supported: first\r\nSupported: second\r\nMax-Forwards: 70\r\n"
+        );
+    }
+}
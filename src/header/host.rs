@@ -0,0 +1,132 @@
+use super::*;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+fn is_host_char(c: u8) -> bool {
+    !b":;?<>@,\"[] \t\r\n".contains(&c)
+}
+
+/// Host part of URIs and Via headers. IPv6 references are written inside brackets.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Host {
+    Domain(String),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+}
+
+impl Host {
+    /// Compares the hosts as RFC 3261 does, where domain names are case-insensitive.
+    pub fn matches(&self, other: &Host) -> bool {
+        match (self, other) {
+            (Host::Domain(a), Host::Domain(b)) => a.eq_ignore_ascii_case(b),
+            _ => self == other,
+        }
+    }
+
+    /// The IP address, when the host is an IP literal instead of a domain name.
+    pub fn ip_addr(&self) -> Option<IpAddr> {
+        match self {
+            Host::Domain(_) => None,
+            Host::Ipv4(ip) => Some(IpAddr::V4(*ip)),
+            Host::Ipv6(ip) => Some(IpAddr::V6(*ip)),
+        }
+    }
+}
+
+impl From<IpAddr> for Host {
+    fn from(ip: IpAddr) -> Host {
+        match ip {
+            IpAddr::V4(ip) => Host::Ipv4(ip),
+            IpAddr::V6(ip) => Host::Ipv6(ip),
+        }
+    }
+}
+
+/// Classifies the given text as an IP literal (with or without the IPv6 brackets) or a domain.
+impl From<&str> for Host {
+    fn from(s: &str) -> Host {
+        let unbracketed = s.trim_start_matches('[').trim_end_matches(']');
+
+        if let Ok(ip) = s.parse::<Ipv4Addr>() {
+            Host::Ipv4(ip)
+        } else if let Ok(ip) = unbracketed.parse::<Ipv6Addr>() {
+            Host::Ipv6(ip)
+        } else {
+            Host::Domain(s.to_owned())
+        }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Host::Domain(name) => write!(f, "{}", name),
+            Host::Ipv4(ip) => write!(f, "{}", ip),
+            Host::Ipv6(ip) => write!(f, "[{}]", ip),
+        }
+    }
+}
+
+named!(
+    pub parse_host<Host>,
+    alt_complete!(
+        map_res!(
+            delimited!(tag!("["), take_until!("]"), tag!("]")),
+            |ip| to_str_default(ip).parse::<Ipv6Addr>().map(Host::Ipv6)
+        ) |
+        map!(take_while1!(is_host_char), |name| Host::from(to_str_default(name).as_str()))
+    )
+);
+
+named!(
+    pub parse_port<u16>,
+    map_res!(take_while1!(nom::is_digit), |port| to_str_default(port).parse::<u16>())
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_kinds() {
+        assert_eq!(
+            parse_host(b"example.com:5060\r\n"),
+            Ok((
+                b":5060\r\n" as &[u8],
+                Host::Domain("example.com".to_owned())
+            ))
+        );
+        assert_eq!(
+            parse_host(b"10.0.0.1;lr\r\n"),
+            Ok((b";lr\r\n" as &[u8], Host::Ipv4(Ipv4Addr::new(10, 0, 0, 1))))
+        );
+        assert_eq!(
+            parse_host(b"[2001:db8::1]:5060\r\n"),
+            Ok((
+                b":5060\r\n" as &[u8],
+                Host::Ipv6("2001:db8::1".parse().unwrap())
+            ))
+        );
+        assert!(parse_host(b"[2001:db8::zz]\r\n").is_err());
+    }
+
+    #[test]
+    fn host_display() {
+        assert_eq!(Host::from("[2001:DB8:0::1]").to_string(), "[2001:db8::1]");
+        assert_eq!(Host::from("::1").to_string(), "[::1]");
+        assert_eq!(Host::from("192.0.2.4").to_string(), "192.0.2.4");
+        assert!(Host::from("Biloxi.COM").matches(&Host::from("biloxi.com")));
+    }
+
+    #[test]
+    fn host_ip_addr() {
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+
+        assert_eq!(Host::from(ip).ip_addr(), Some(ip));
+        assert_eq!(
+            Host::from("10.0.0.1").ip_addr(),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(Host::from("example.com").ip_addr(), None);
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+use std::fmt;
+
+/// Request method, as found on the request line and on `CSeq`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Method {
+    Register,
+    Invite,
+    Ack,
+    Cancel,
+    Bye,
+    Options,
+    Subscribe,
+    Notify,
+    Refer,
+    Message,
+    Info,
+    Prack,
+    Update,
+    Publish,
+    Unknown(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Register => "REGISTER",
+            Method::Invite => "INVITE",
+            Method::Ack => "ACK",
+            Method::Cancel => "CANCEL",
+            Method::Bye => "BYE",
+            Method::Options => "OPTIONS",
+            Method::Subscribe => "SUBSCRIBE",
+            Method::Notify => "NOTIFY",
+            Method::Refer => "REFER",
+            Method::Message => "MESSAGE",
+            Method::Info => "INFO",
+            Method::Prack => "PRACK",
+            Method::Update => "UPDATE",
+            Method::Publish => "PUBLISH",
+            Method::Unknown(method) => method,
+        }
+    }
+}
+
+/// Methods are case-sensitive, so `invite` is an unknown method and not `INVITE`.
+impl From<&str> for Method {
+    fn from(method: &str) -> Method {
+        match method {
+            "REGISTER" => Method::Register,
+            "INVITE" => Method::Invite,
+            "ACK" => Method::Ack,
+            "CANCEL" => Method::Cancel,
+            "BYE" => Method::Bye,
+            "OPTIONS" => Method::Options,
+            "SUBSCRIBE" => Method::Subscribe,
+            "NOTIFY" => Method::Notify,
+            "REFER" => Method::Refer,
+            "MESSAGE" => Method::Message,
+            "INFO" => Method::Info,
+            "PRACK" => Method::Prack,
+            "UPDATE" => Method::Update,
+            "PUBLISH" => Method::Publish,
+            _ => Method::Unknown(method.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+named!(
+    pub parse_method<Method>,
+    map!(take_while1!(is_not_reserved_char), |m| Method::from(
+        to_str_default(m).as_str()
+    ))
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn methods_round_trip() {
+        let names = [
+            "REGISTER",
+            "INVITE",
+            "ACK",
+            "CANCEL",
+            "BYE",
+            "OPTIONS",
+            "SUBSCRIBE",
+            "NOTIFY",
+            "REFER",
+            "MESSAGE",
+            "INFO",
+            "PRACK",
+            "UPDATE",
+            "PUBLISH",
+        ];
+
+        for name in names.iter() {
+            let method = Method::from(*name);
+            assert_ne!(method, Method::Unknown(name.to_string()), "{}", name);
+            assert_eq!(method.to_string(), *name);
+        }
+
+        assert_eq!(Method::from("invite"), Method::Unknown("invite".to_owned()));
+        assert_eq!(
+            parse_method(b"X-CUSTOM sip:a@b\r\n"),
+            Ok((
+                b" sip:a@b\r\n" as &[u8],
+                Method::Unknown("X-CUSTOM".to_owned())
+            ))
+        );
+    }
+}
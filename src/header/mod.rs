@@ -1,33 +1,129 @@
 use nom::*;
 
+mod auth;
+mod auth_client;
+mod collection;
+mod host;
+mod method;
+mod name_addr;
+mod params;
+mod route;
+mod tel;
 mod types;
+mod uri;
+mod via;
+pub use self::auth::*;
+pub use self::auth_client::*;
+pub use self::collection::*;
+pub use self::host::*;
+pub use self::method::*;
+pub use self::name_addr::*;
+pub use self::params::*;
+pub use self::route::*;
+pub use self::tel::*;
 pub use self::types::*;
+pub use self::uri::*;
+pub use self::via::*;
 
 use super::*;
-use std::collections::HashMap;
 use std::fmt;
-use std::io::Read;
+use std::io::{self, BufReader, Read, Write};
 use std::iter::{IntoIterator, Iterator};
 
-type SipResult<T> = Result<T, MessageParserError>;
+pub type SipResult<T> = Result<T, MessageParserError>;
+
+/// Compact forms of header names (RFC 3261 section 7.3.3 and later extensions).
+pub const COMPACT_HEADER_NAMES: &[(&str, &str)] = &[
+    ("a", "Accept-Contact"),
+    ("b", "Referred-By"),
+    ("c", "Content-Type"),
+    ("d", "Request-Disposition"),
+    ("e", "Content-Encoding"),
+    ("f", "From"),
+    ("i", "Call-ID"),
+    ("j", "Reject-Contact"),
+    ("k", "Supported"),
+    ("l", "Content-Length"),
+    ("m", "Contact"),
+    ("n", "Identity-Info"),
+    ("o", "Event"),
+    ("r", "Refer-To"),
+    ("s", "Subject"),
+    ("t", "To"),
+    ("u", "Allow-Events"),
+    ("v", "Via"),
+    ("x", "Session-Expires"),
+    ("y", "Identity"),
+];
+
+/// Full header name for a compact one, or the name itself when it isn't a compact form.
+pub fn expand_compact_name(name: &str) -> &str {
+    COMPACT_HEADER_NAMES
+        .iter()
+        .find(|(compact, _)| compact.eq_ignore_ascii_case(name))
+        .map(|(_, full)| *full)
+        .unwrap_or(name)
+}
+
+/// Compact form of a full header name, if there is one.
+pub fn compact_name(name: &str) -> Option<&'static str> {
+    COMPACT_HEADER_NAMES
+        .iter()
+        .find(|(_, full)| full.eq_ignore_ascii_case(name))
+        .map(|(compact, _)| *compact)
+}
+
+/// Whether two header names, in full or compact form, refer to the same header. Header names
+/// are case-insensitive.
+pub fn is_same_header_name(a: &str, b: &str) -> bool {
+    expand_compact_name(a).eq_ignore_ascii_case(expand_compact_name(b))
+}
+
+fn expand_compact_name_bytes(name: &[u8]) -> &[u8] {
+    COMPACT_HEADER_NAMES
+        .iter()
+        .find(|(compact, _)| compact.as_bytes().eq_ignore_ascii_case(name))
+        .map(|(_, full)| full.as_bytes())
+        .unwrap_or(name)
+}
+
+//Key used to match header names against the known ones, regardless of case or compact form
+fn header_key(name: &[u8]) -> Vec<u8> {
+    expand_compact_name_bytes(name).to_ascii_lowercase()
+}
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum SipHeader {
-    Contact(ContactInfo),
-    To(ContactInfo),
-    From(ContactInfo),
+    Contact(Contact),
+    To(NameAddr),
+    From(NameAddr),
+    Route(NameAddr),
+    RecordRoute(NameAddr),
+    CallInfo(NameAddr),
+    AlertInfo(NameAddr),
+    Geolocation(NameAddr),
+    PAssertedIdentity(NameAddr),
     Expires(u32),
     MaxForwards(u32),
     ContentLength(u32),
     CallID(String),
-    Accept(String),
+    Accept {
+        value: String,
+        params: Params,
+    },
     UserAgent(String),
-    Event(String),
+    Event {
+        value: String,
+        params: Params,
+    },
     Allow(Vec<String>),
     AllowEvents(Vec<String>),
     Supported(Vec<String>),
-    Authorization(Vec<String>),
-    WWWAuthenticate(Vec<String>),
+    Authorization(Credentials),
+    WWWAuthenticate(Challenge),
+    ProxyAuthorization(Credentials),
+    ProxyAuthenticate(Challenge),
+    AuthenticationInfo(AuthenticationInfo),
     SessionID(String),
     Server(String),
     Date(String),
@@ -35,20 +131,19 @@ pub enum SipHeader {
     Require(Vec<String>),
     AcceptLanguage(String),
 
-    MinSE(u32), //Minimum value for Session-Expires
-    SessionExpires {
-        value: u32,
+    MinSE {
+        value: u32, //Minimum value for Session-Expires
         params: Params,
     },
-
-    Via {
-        protocol: String,
-        addr: SockAddr,
+    SessionExpires {
+        value: u32, //Delta seconds until the session expires
         params: Params,
     },
+
+    Via(Via),
     CSeq {
         seq: u32,
-        header: String,
+        method: Method,
     },
 
     Unknown {
@@ -57,33 +152,103 @@ pub enum SipHeader {
     },
 }
 
-#[derive(PartialEq, Debug)]
+impl SipHeader {
+    /// Compact form of this header name, if there is one.
+    pub fn compact_name(&self) -> Option<&'static str> {
+        compact_name(self.name())
+    }
+
+    /// Canonical name of this header, as used when it is added through the API. Compact names
+    /// are always expanded to the full form.
+    pub fn name(&self) -> &str {
+        match self {
+            SipHeader::Contact(_) => "Contact",
+            SipHeader::To(_) => "To",
+            SipHeader::From(_) => "From",
+            SipHeader::Route(_) => "Route",
+            SipHeader::RecordRoute(_) => "Record-Route",
+            SipHeader::CallInfo(_) => "Call-Info",
+            SipHeader::AlertInfo(_) => "Alert-Info",
+            SipHeader::Geolocation(_) => "Geolocation",
+            SipHeader::PAssertedIdentity(_) => "P-Asserted-Identity",
+            SipHeader::Expires(_) => "Expires",
+            SipHeader::MaxForwards(_) => "Max-Forwards",
+            SipHeader::ContentLength(_) => "Content-Length",
+            SipHeader::CallID(_) => "Call-ID",
+            SipHeader::Accept { .. } => "Accept",
+            SipHeader::UserAgent(_) => "User-Agent",
+            SipHeader::Event { .. } => "Event",
+            SipHeader::Allow(_) => "Allow",
+            SipHeader::AllowEvents(_) => "Allow-Events",
+            SipHeader::Supported(_) => "Supported",
+            SipHeader::Authorization(_) => "Authorization",
+            SipHeader::WWWAuthenticate(_) => "WWW-Authenticate",
+            SipHeader::ProxyAuthorization(_) => "Proxy-Authorization",
+            SipHeader::ProxyAuthenticate(_) => "Proxy-Authenticate",
+            SipHeader::AuthenticationInfo(_) => "Authentication-Info",
+            SipHeader::SessionID(_) => "Session-ID",
+            SipHeader::Server(_) => "Server",
+            SipHeader::Date(_) => "Date",
+            SipHeader::ContentType(_) => "Content-Type",
+            SipHeader::Require(_) => "Require",
+            SipHeader::AcceptLanguage(_) => "Accept-Language",
+            SipHeader::MinSE { .. } => "Min-SE",
+            SipHeader::SessionExpires { .. } => "Session-Expires",
+            SipHeader::Via(_) => "Via",
+            SipHeader::CSeq { .. } => "CSeq",
+            SipHeader::Unknown { name, .. } => name,
+        }
+    }
+}
+
+/// Writes the header value (without the name) in wire format.
+impl fmt::Display for SipHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SipHeader::Contact(c) => write!(f, "{}", c),
+            SipHeader::To(a)
+            | SipHeader::From(a)
+            | SipHeader::Route(a)
+            | SipHeader::RecordRoute(a)
+            | SipHeader::CallInfo(a)
+            | SipHeader::AlertInfo(a)
+            | SipHeader::Geolocation(a)
+            | SipHeader::PAssertedIdentity(a) => write!(f, "{}", a),
+            SipHeader::Expires(u) | SipHeader::MaxForwards(u) | SipHeader::ContentLength(u) => {
+                write!(f, "{}", u)
+            }
+            SipHeader::CallID(s)
+            | SipHeader::UserAgent(s)
+            | SipHeader::SessionID(s)
+            | SipHeader::Server(s)
+            | SipHeader::Date(s)
+            | SipHeader::ContentType(s)
+            | SipHeader::AcceptLanguage(s) => write!(f, "{}", s),
+            SipHeader::Allow(l)
+            | SipHeader::AllowEvents(l)
+            | SipHeader::Supported(l)
+            | SipHeader::Require(l) => write!(f, "{}", l.join(", ")),
+            SipHeader::Authorization(c) | SipHeader::ProxyAuthorization(c) => write!(f, "{}", c),
+            SipHeader::WWWAuthenticate(c) | SipHeader::ProxyAuthenticate(c) => write!(f, "{}", c),
+            SipHeader::AuthenticationInfo(i) => write!(f, "{}", i),
+            SipHeader::Accept { value, params } | SipHeader::Event { value, params } => {
+                write!(f, "{}{}", value, params)
+            }
+            SipHeader::MinSE { value, params } | SipHeader::SessionExpires { value, params } => {
+                write!(f, "{}{}", value, params)
+            }
+            SipHeader::Via(via) => write!(f, "{}", via),
+            SipHeader::CSeq { seq, method } => write!(f, "{} {}", seq, method),
+            SipHeader::Unknown { value, .. } => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Start line of a message, which is either a request or a response.
+#[derive(PartialEq, Debug, Clone)]
 pub enum SipMethod {
-    Register {
-        uri: URI,
-        version: String,
-    },
-    Invite {
-        uri: URI,
-        version: String,
-    },
-    Subscribe {
-        uri: URI,
-        version: String,
-    },
-    Ack {
-        uri: URI,
-        version: String,
-    },
-    Cancel {
-        uri: URI,
-        version: String,
-    },
-    Bye {
-        uri: URI,
-        version: String,
-    },
-    Options {
+    Request {
+        method: Method,
         uri: URI,
         version: String,
     },
@@ -92,38 +257,45 @@ pub enum SipMethod {
         code: u32,
         reason: String,
     },
-    Unknown {
-        method: String,
-        uri: URI,
-        version: String,
-    },
 }
 
 impl SipMethod {
-    fn new_req(method: String, uri: URI, version: String) -> SipMethod {
-        match method.as_ref() {
-            "REGISTER" => SipMethod::Register { uri, version },
-            "INVITE" => SipMethod::Invite { uri, version },
-            "ACK" => SipMethod::Ack { uri, version },
-            "CANCEL" => SipMethod::Cancel { uri, version },
-            "BYE" => SipMethod::Bye { uri, version },
-            "OPTIONS" => SipMethod::Options { uri, version },
-            "SUBSCRIBE" => SipMethod::Subscribe { uri, version },
-            _ => SipMethod::Unknown {
+    /// Method of a request. `None` for responses, whose method is only found on `CSeq`.
+    pub fn method(&self) -> Option<&Method> {
+        match self {
+            SipMethod::Request { method, .. } => Some(method),
+            SipMethod::Response { .. } => None,
+        }
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.method().is_some()
+    }
+}
+
+/// Writes the start line (without the trailing CRLF) in wire format.
+impl fmt::Display for SipMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SipMethod::Request {
                 method,
                 uri,
                 version,
-            },
+            } => write!(f, "{} {} {}", method, uri, version),
+            SipMethod::Response {
+                version,
+                code,
+                reason,
+            } => write!(f, "{} {} {}", version, code, reason),
         }
     }
 }
 
 #[derive(Debug, Fail)]
-enum MessageParserError {
+pub enum MessageParserError {
     #[fail(
         display = "Error at parsing near: {}. Remaining: {1}",
-        detail,
-        remaining
+        detail, remaining
     )]
     Parse { detail: String, remaining: String },
 
@@ -132,6 +304,15 @@ enum MessageParserError {
 
     #[fail(display = "EOF Reached!")]
     EOF,
+
+    #[fail(display = "EOF reached in the middle of a message")]
+    UnexpectedEof,
+
+    #[fail(display = "Message exceeds the maximum size of {} bytes", limit)]
+    MessageTooLarge { limit: usize },
+
+    #[fail(display = "Invalid SDP line \"{}\": {}", line, detail)]
+    Sdp { line: String, detail: String },
 }
 
 impl From<std::io::Error> for MessageParserError {
@@ -142,7 +323,7 @@ impl From<std::io::Error> for MessageParserError {
 
 impl<'a> From<nom::Err<&'a [u8]>> for MessageParserError {
     fn from(error: nom::Err<&'a [u8]>) -> Self {
-        if let nom::Err::Error(c) = error {
+        if let nom::Err::Error(c) | nom::Err::Failure(c) = error {
             match c {
                 nom::Context::Code(remaining, detail) => MessageParserError::Parse {
                     remaining: to_str_default(remaining).to_string(),
@@ -158,51 +339,139 @@ impl<'a> From<nom::Err<&'a [u8]>> for MessageParserError {
     }
 }
 
+#[derive(PartialEq, Clone)]
 pub struct SipMessage {
     pub method: SipMethod,
-    pub headers: HashMap<String, SipHeader>,
-    pub content: Vec<String>,
+    pub headers: SipHeaders,
+    pub body: Vec<u8>,
+}
+
+impl SipMessage {
+    /// Writes the whole message in wire format.
+    pub fn encode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.encode_with(w, false)
+    }
+
+    /// Writes the whole message in wire format, using the compact form of every header name
+    /// which has one. Headers kept in their original text are still written as they were.
+    pub fn encode_compact<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.encode_with(w, true)
+    }
+
+    fn encode_with<W: Write>(&self, w: &mut W, compact: bool) -> io::Result<()> {
+        write!(w, "{}\r\n", self.method)?;
+
+        if compact {
+            self.headers.encode_compact(w)?;
+        } else {
+            self.headers.encode(w)?;
+        }
+
+        w.write_all(b"\r\n")?;
+        w.write_all(&self.body)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf)
+            .expect("Writing to a Vec should never fail");
+        buf
+    }
+
+    /// Replaces the body, keeping the `Content-Length` header in sync with it.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.headers
+            .set(SipHeader::ContentLength(body.len() as u32));
+        self.body = body;
+    }
+
+    /// Value of the first `Content-Length` header, if any.
+    pub fn content_length(&self) -> Option<u32> {
+        match self.headers.first("Content-Length") {
+            Some(SipHeader::ContentLength(len)) => Some(*len),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for SipMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "SipMessage {{ method: {0:#?}, headers: {1:#?}, content: {2:#?} }}",
-            self.method, self.headers, self.content
+            "SipMessage {{ method: {0:#?}, headers: {1:#?}, body: {2:#?} }}",
+            self.method,
+            self.headers,
+            String::from_utf8_lossy(&self.body)
         )
     }
 }
 
+/// Writes the message in wire format. Bodies which aren't valid UTF-8 are written lossily, use
+/// `SipMessage::encode` to get the exact bytes.
 impl fmt::Display for SipMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "SipMessage {{ method: {0:?}, headers: {1:?}, content: {2:?} }}",
-            self.method, self.headers, self.content
+            "{}\r\n{}\r\n{}",
+            self.method,
+            self.headers,
+            String::from_utf8_lossy(&self.body)
         )
     }
 }
 
-struct MessageParser<R: Read> {
-    bytes: std::iter::Peekable<std::io::Bytes<R>>,
+/// Default limit, in bytes, for a whole message (start line, headers and body).
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 65535;
+
+/// Streaming parser which reads SIP messages, one after another, from any `Read` source.
+///
+/// Messages can be pulled with `next_message` or by iterating over the parser, which yields
+/// `Ok` for each parsed message and ends once the stream is exhausted or after the first `Err`.
+pub struct MessageParser<R: Read> {
+    bytes: std::iter::Peekable<std::io::Bytes<BufReader<R>>>,
+    done: bool,
+    max_message_size: usize,
+    message_size: usize,
+    preserve_raw_headers: bool,
 }
 
 impl<R: Read> MessageParser<R> {
-    fn new(stream: R) -> MessageParser<R> {
+    pub fn new(stream: R) -> MessageParser<R> {
+        MessageParser::with_max_message_size(stream, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Creates a parser which fails with `MessageParserError::MessageTooLarge` on any message
+    /// bigger than `max_message_size` bytes.
+    pub fn with_max_message_size(stream: R, max_message_size: usize) -> MessageParser<R> {
         MessageParser {
-            bytes: stream.bytes().peekable(),
+            bytes: BufReader::new(stream).bytes().peekable(),
+            done: false,
+            max_message_size,
+            message_size: 0,
+            preserve_raw_headers: false,
         }
     }
 
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// When enabled, each parsed header keeps the original line it was read from, so encoding
+    /// an unmodified header writes it back byte for byte.
+    pub fn set_preserve_raw_headers(&mut self, preserve: bool) {
+        self.preserve_raw_headers = preserve;
+    }
+
+    pub fn preserve_raw_headers(&self) -> bool {
+        self.preserve_raw_headers
+    }
+
     fn skip_empty_linebreak(&mut self) -> Option<()> {
         let mut skip = false;
 
-        if let Some(byte) = self.bytes.peek() {
-            if let Ok(b) = byte {
-                if *b == b'\r' {
-                    skip = true;
-                }
+        if let Some(Ok(b)) = self.bytes.peek() {
+            if *b == b'\r' {
+                skip = true;
             }
         }
 
@@ -214,76 +483,156 @@ impl<R: Read> MessageParser<R> {
         None
     }
 
-    fn read_until(&mut self, buf: &mut [u8], delim: &[u8]) -> SipResult<usize> {
-        let mut i = 0;
+    fn too_large(&self) -> MessageParserError {
+        MessageParserError::MessageTooLarge {
+            limit: self.max_message_size,
+        }
+    }
 
-        while let Some(byte) = self.bytes.next() {
-            buf[i] = byte?;
-            i += 1;
+    fn next_byte(&mut self) -> SipResult<u8> {
+        if self.message_size >= self.max_message_size {
+            return Err(self.too_large());
+        }
 
-            if i > delim.len() {
-                let len = delim.len();
-                if delim == &buf[i - len..i] {
-                    return Ok(i);
-                }
+        match self.bytes.next() {
+            Some(byte) => {
+                self.message_size += 1;
+                Ok(byte?)
             }
+            None => Err(MessageParserError::EOF),
         }
+    }
+
+    /// Appends bytes to `buf` until the bytes appended by this call end with `delim`.
+    fn read_until(&mut self, buf: &mut Vec<u8>, delim: &[u8]) -> SipResult<()> {
+        let start = buf.len();
 
-        Err(MessageParserError::EOF)
+        loop {
+            buf.push(self.next_byte()?);
+
+            if buf[start..].ends_with(delim) {
+                return Ok(());
+            }
+        }
     }
 
     fn read_method(&mut self) -> SipResult<SipMethod> {
-        let mut buf = [0u8; 1024];
-        let rc = self.read_until(&mut buf, b"\r\n")?;
+        let mut buf = vec![];
+        self.read_until(&mut buf, b"\r\n")?;
 
-        let res = parse_sip_method(&buf[0..rc])?;
+        let res = parse_sip_method(&buf)?;
 
         Ok(res.1)
     }
 
-    fn read_headers(&mut self) -> SipResult<HashMap<String, SipHeader>> {
-        let mut buf = [0u8; 1024];
-        let rc = self.read_until(&mut buf, b"\r\n\r\n")?;
+    fn read_headers(&mut self) -> SipResult<SipHeaders> {
+        let mut buf = vec![];
 
-        let res = parse_sip_headers(&buf[0..rc])?;
+        //Headers end on the first empty line
+        loop {
+            let start = buf.len();
+            self.read_until(&mut buf, b"\r\n")?;
 
-        Ok(res.1)
-    }
+            if buf.len() - start == 2 {
+                break;
+            }
+        }
 
-    fn read_contents(&mut self) -> SipResult<Vec<String>> {
-        let mut buf = [0u8; 1024];
-        let rc = self.read_until(&mut buf, b"\r\n\r\n")?;
+        let lines = parse_sip_headers(&buf)?;
+        let mut headers = SipHeaders::new();
 
-        let res = parse_sip_contents(&buf[0..rc])?;
+        for (raw, values) in lines {
+            for (i, (name, header)) in values.into_iter().enumerate() {
+                match (self.preserve_raw_headers, i) {
+                    (false, _) => headers.push(name, header),
+                    (true, 0) => headers.push_raw(name, header, raw.to_vec()),
+                    (true, _) => headers.push_same_line(name, header),
+                }
+            }
+        }
 
-        Ok(res.1)
+        Ok(headers)
     }
 
-    fn get_next(&mut self) -> SipResult<SipMessage> {
-        while let Some(()) = self.skip_empty_linebreak() {}
+    fn read_body(&mut self, len: usize) -> SipResult<Vec<u8>> {
+        if self.message_size + len > self.max_message_size {
+            return Err(self.too_large());
+        }
+
+        let mut body = Vec::with_capacity(len);
+
+        for byte in self.bytes.by_ref().take(len) {
+            body.push(byte?);
+        }
+
+        self.message_size += body.len();
 
+        if body.len() < len {
+            return Err(MessageParserError::EOF);
+        }
+
+        Ok(body)
+    }
+
+    fn read_message(&mut self) -> SipResult<SipMessage> {
         let method = self.read_method()?;
         let headers = self.read_headers()?;
 
-        let content = if let Some(SipHeader::ContentLength(_)) = headers.get("Content-Length") {
-            self.read_contents()?
-        } else {
-            vec![]
+        //A missing Content-Length is treated as an empty body
+        let body = match headers.first("Content-Length") {
+            Some(SipHeader::ContentLength(len)) if *len > 0 => self.read_body(*len as usize)?,
+            _ => vec![],
         };
 
         Ok(SipMessage {
             method,
             headers,
-            content,
+            body,
+        })
+    }
+
+    /// Reads the next message from the stream.
+    ///
+    /// Returns `MessageParserError::EOF` when the stream ends cleanly between messages and
+    /// `MessageParserError::UnexpectedEof` when it ends in the middle of one. After any other
+    /// error the stream is left in the middle of the failed message, so it can't be resumed.
+    pub fn next_message(&mut self) -> SipResult<SipMessage> {
+        while let Some(()) = self.skip_empty_linebreak() {}
+
+        if self.bytes.peek().is_none() {
+            return Err(MessageParserError::EOF);
+        }
+
+        self.message_size = 0;
+
+        self.read_message().map_err(|e| match e {
+            MessageParserError::EOF => MessageParserError::UnexpectedEof,
+            e => e,
         })
     }
 }
 
 impl<R: Read> Iterator for MessageParser<R> {
-    type Item = SipMessage;
+    type Item = SipResult<SipMessage>;
+
+    fn next(&mut self) -> Option<SipResult<SipMessage>> {
+        if self.done {
+            return None;
+        }
 
-    fn next(&mut self) -> Option<SipMessage> {
-        self.get_next().ok()
+        match self.next_message() {
+            Err(MessageParserError::EOF) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                //There is no way to resync after a failure, since the body of the failed message
+                //can't be framed and would be read as the next message
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(msg) => Some(Ok(msg)),
+        }
     }
 }
 
@@ -316,32 +665,32 @@ named!(
 
 named!(
     parse_accept_header<SipHeader>,
-    do_parse!(s: parse_str >> (SipHeader::Accept(s)))
+    do_parse!(value: parse_str >> params: parse_params >> (SipHeader::Accept { value, params }))
 );
 
 named!(
     parse_user_agent_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::UserAgent(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::UserAgent(s)))
 );
 
 named!(
     parse_session_id_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::SessionID(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::SessionID(s)))
 );
 
 named!(
     parse_server_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::Server(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::Server(s)))
 );
 
 named!(
     parse_date_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::Date(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::Date(s)))
 );
 
 named!(
     parse_content_type_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::ContentType(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::ContentType(s)))
 );
 
 named!(
@@ -353,17 +702,17 @@ named!(
 
 named!(
     parse_accept_language_header<SipHeader>,
-    do_parse!(s: take_until!("\r\n") >> (SipHeader::AcceptLanguage(to_str(s).unwrap_or_default())))
+    do_parse!(s: parse_str_line >> (SipHeader::AcceptLanguage(s)))
 );
 
 named!(
     parse_min_se_header<SipHeader>,
-    do_parse!(u: parse_u32 >> (SipHeader::MinSE(u)))
+    do_parse!(value: parse_u32 >> params: parse_params >> (SipHeader::MinSE { value, params }))
 );
 
 named!(
     parse_event_header<SipHeader>,
-    do_parse!(s: parse_str >> (SipHeader::Event(s)))
+    do_parse!(value: parse_str >> params: parse_params >> (SipHeader::Event { value, params }))
 );
 
 named!(
@@ -373,12 +722,42 @@ named!(
 
 named!(
     parse_to_header<SipHeader>,
-    do_parse!(contact: parse_contact >> (SipHeader::To(contact)))
+    do_parse!(addr: parse_name_addr >> (SipHeader::To(addr)))
 );
 
 named!(
     parse_from_header<SipHeader>,
-    do_parse!(contact: parse_contact >> (SipHeader::From(contact)))
+    do_parse!(addr: parse_name_addr >> (SipHeader::From(addr)))
+);
+
+named!(
+    parse_route_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::Route(addr)))
+);
+
+named!(
+    parse_record_route_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::RecordRoute(addr)))
+);
+
+named!(
+    parse_call_info_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::CallInfo(addr)))
+);
+
+named!(
+    parse_alert_info_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::AlertInfo(addr)))
+);
+
+named!(
+    parse_geolocation_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::Geolocation(addr)))
+);
+
+named!(
+    parse_p_asserted_identity_header<SipHeader>,
+    do_parse!(addr: parse_name_addr >> (SipHeader::PAssertedIdentity(addr)))
 );
 
 named!(
@@ -391,50 +770,52 @@ named!(
     do_parse!(u32h: parse_u32 >> (SipHeader::MaxForwards(u32h)))
 );
 
+//Unlike other numbers, an invalid length is an error, since the body can't be framed without it
 named!(
     parse_content_length_header<SipHeader>,
-    do_parse!(u32h: parse_u32 >> (SipHeader::ContentLength(u32h)))
-);
-
-//Complex header parsing
-named!(
-    parse_via_header<SipHeader>,
     do_parse!(
         take_while!(nom::is_space)
-            >> p: preceded!(tag!("SIP/2.0/"), take_till!(nom::is_space))
-            >> tag!(" ")
-            >> addr: call!(parse_sock_addr)
-            >> params: call!(parse_params) >> (SipHeader::Via {
-            protocol: to_str_default(p),
-            addr,
-            params,
-        })
+            >> len: map_res!(map_res!(digit, std::str::from_utf8), str::parse::<u32>)
+            >> (SipHeader::ContentLength(len))
     )
 );
 
+//Complex header parsing
+named!(parse_via_header<SipHeader>, map!(parse_via, SipHeader::Via));
+
 named!(
     parse_cseq_header<SipHeader>,
-    do_parse!(s: parse_u32 >> tag!(" ") >> h: parse_str >> (SipHeader::CSeq { seq: s, header: h }))
+    do_parse!(
+        seq: parse_u32
+            >> take_while1!(nom::is_space)
+            >> method: parse_method
+            >> (SipHeader::CSeq { seq, method })
+    )
 );
 
 named!(
     parse_authorization_header<SipHeader>,
-    do_parse!(
-        opt!(take_while!(nom::is_space))
-            >> tag!("Digest ")
-            >> l: parse_str_list
-            >> (SipHeader::Authorization(l))
-    )
+    map!(parse_credentials, SipHeader::Authorization)
 );
 
 named!(
     parse_www_authenticate_header<SipHeader>,
-    do_parse!(
-        opt!(take_while!(nom::is_space))
-            >> tag!("Digest ")
-            >> l: parse_str_list
-            >> (SipHeader::WWWAuthenticate(l))
-    )
+    map!(parse_challenge, SipHeader::WWWAuthenticate)
+);
+
+named!(
+    parse_proxy_authorization_header<SipHeader>,
+    map!(parse_credentials, SipHeader::ProxyAuthorization)
+);
+
+named!(
+    parse_proxy_authenticate_header<SipHeader>,
+    map!(parse_challenge, SipHeader::ProxyAuthenticate)
+);
+
+named!(
+    parse_authentication_info_header<SipHeader>,
+    map!(parse_authentication_info, SipHeader::AuthenticationInfo)
 );
 
 //Method parsing
@@ -446,11 +827,16 @@ named!(
 named!(
     parse_sip_request<SipMethod>,
     do_parse!(
-        m: complete!(take_until_and_consume!(" "))
+        method: parse_method
+            >> tag!(" ")
             >> uri: parse_uri
-            >> v: parse_str
+            >> version: parse_str
             >> tag!("\r\n")
-            >> (SipMethod::new_req(to_str(m).unwrap_or_default(), uri, v))
+            >> (SipMethod::Request {
+                method,
+                uri,
+                version,
+            })
     )
 );
 
@@ -474,61 +860,230 @@ named!(
 named!(
     parse_sip_header<(String, SipHeader)>,
     do_parse!(
-        take_while!(nom::is_space) >> name: take_until_and_consume!(":")
+        take_while!(nom::is_space)
+            >> name: map!(take_until_and_consume!(":"), trim_end_spaces)
+            >> key: value!(header_key(name))
             >> header:
-                complete!(switch!(value!(name),
-                              b"Contact" => call!(parse_contact_header)
-                              | b"To" => call!(parse_to_header)
-                              | b"From" => call!(parse_from_header)
-                              | b"Expires" => call!(parse_expires_header)
-                              | b"Max-Forwards" => call!(parse_max_forwards_header)
-                              | b"Content-Length" => call!(parse_content_length_header)
-                              | b"Call-ID" => call!(parse_call_id_header)
-                              | b"CSeq" => call!(parse_cseq_header)
-                              | b"Accept" => call!(parse_accept_header)
-                              | b"User-Agent" => call!(parse_user_agent_header)
-                              | b"Event" => call!(parse_event_header)
-                              | b"Allow" => call!(parse_allow_header)
-                              | b"Allow-Events" => call!(parse_allow_events_header)
-                              | b"Via" => call!(parse_via_header)
-                              | b"Supported" => call!(parse_supported_header)
-                              | b"Authorization" => call!(parse_authorization_header)
-                              | b"Session-ID" => call!(parse_session_id_header)
-                              | b"Server" => call!(parse_server_header)
-                              | b"WWW-Authenticate" => call!(parse_www_authenticate_header)
-                              | b"Date" => call!(parse_date_header)
-                              | b"Content-Type" => call!(parse_content_type_header)
-                              | b"Session-Expires" => call!(parse_session_expires_header)
-                              | b"Require" => call!(parse_require_header)
-                              | b"Accept-Language" => call!(parse_accept_language_header)
-                              | b"Min-SE" => call!(parse_min_se_header)
+                complete!(switch!(value!(&key[..]),
+                              b"contact" => call!(parse_contact_header)
+                              | b"to" => call!(parse_to_header)
+                              | b"from" => call!(parse_from_header)
+                              | b"route" => call!(parse_route_header)
+                              | b"record-route" => call!(parse_record_route_header)
+                              | b"call-info" => call!(parse_call_info_header)
+                              | b"alert-info" => call!(parse_alert_info_header)
+                              | b"geolocation" => call!(parse_geolocation_header)
+                              | b"p-asserted-identity" => call!(parse_p_asserted_identity_header)
+                              | b"expires" => call!(parse_expires_header)
+                              | b"max-forwards" => call!(parse_max_forwards_header)
+                              | b"content-length" => call!(parse_content_length_header)
+                              | b"call-id" => call!(parse_call_id_header)
+                              | b"cseq" => call!(parse_cseq_header)
+                              | b"accept" => call!(parse_accept_header)
+                              | b"user-agent" => call!(parse_user_agent_header)
+                              | b"event" => call!(parse_event_header)
+                              | b"allow" => call!(parse_allow_header)
+                              | b"allow-events" => call!(parse_allow_events_header)
+                              | b"via" => call!(parse_via_header)
+                              | b"supported" => call!(parse_supported_header)
+                              | b"authorization" => call!(parse_authorization_header)
+                              | b"session-id" => call!(parse_session_id_header)
+                              | b"server" => call!(parse_server_header)
+                              | b"www-authenticate" => call!(parse_www_authenticate_header)
+                              | b"proxy-authorization" => call!(parse_proxy_authorization_header)
+                              | b"proxy-authenticate" => call!(parse_proxy_authenticate_header)
+                              | b"authentication-info" => call!(parse_authentication_info_header)
+                              | b"date" => call!(parse_date_header)
+                              | b"content-type" => call!(parse_content_type_header)
+                              | b"session-expires" => call!(parse_session_expires_header)
+                              | b"require" => call!(parse_require_header)
+                              | b"accept-language" => call!(parse_accept_language_header)
+                              | b"min-se" => call!(parse_min_se_header)
                               | _ => do_parse!(
                                   value: parse_str_line
-                                      >> (SipHeader::Unknown{name: to_str_default(name), value})
+                                      >> (SipHeader::Unknown{
+                                          name: to_str_default(expand_compact_name_bytes(name)),
+                                          value
+                                      })
                               )
             )) >> (String::from_utf8(name.to_vec()).unwrap_or_default(), header)
     )
 );
 
-named!(
-    parse_sip_headers<HashMap<String, SipHeader>>,
-    do_parse!(
-        h: many_till!(
-            do_parse!(i: parse_sip_header >> opt!(tag!("\r\n")) >> (i)),
-            tag!("\r\n")
-        ) >> (h.0.into_iter().collect())
-    )
-);
+//Splits the header section in logical lines, each one with its folded continuation lines but
+//without the final CRLF. Stops at the empty line which ends the section.
+fn split_header_lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines = vec![];
+    let mut start = 0;
+    let mut i = 0;
 
-named!(
-    parse_sip_contents<Vec<String>>,
-    do_parse!(
-        h: many_till!(
-            do_parse!(i: parse_str_line >> opt!(tag!("\r\n")) >> (i)),
-            tag!("\r\n")
-        ) >> (h.0)
+    while i + 1 < data.len() {
+        if &data[i..i + 2] != b"\r\n" {
+            i += 1;
+            continue;
+        }
+
+        let folded = i > start && data.get(i + 2).is_some_and(|c| nom::is_space(*c));
+
+        if !folded {
+            if i == start {
+                break;
+            }
+
+            lines.push(&data[start..i]);
+            start = i + 2;
+        }
+
+        i += 2;
+    }
+
+    lines
+}
+
+//Replaces each line fold (CRLF followed by whitespace) and the whitespace around it by a single SP
+fn unfold_line(line: &[u8]) -> Vec<u8> {
+    let mut unfolded = Vec::with_capacity(line.len());
+    let mut i = 0;
+
+    while i < line.len() {
+        if line[i..].starts_with(b"\r\n") {
+            while unfolded.last().is_some_and(|c| nom::is_space(*c)) {
+                unfolded.pop();
+            }
+
+            unfolded.push(b' ');
+            i += 2;
+
+            while i < line.len() && nom::is_space(line[i]) {
+                i += 1;
+            }
+        } else {
+            unfolded.push(line[i]);
+            i += 1;
+        }
+    }
+
+    unfolded
+}
+
+//Headers whose values may be given as a comma-separated list on a single line
+fn is_list_header(key: &[u8]) -> bool {
+    matches!(
+        key,
+        b"via"
+            | b"contact"
+            | b"accept"
+            | b"route"
+            | b"record-route"
+            | b"call-info"
+            | b"alert-info"
+            | b"geolocation"
+            | b"p-asserted-identity"
     )
-);
+}
+
+//Splits a comma-separated list of values, ignoring commas inside quoted strings and angle brackets
+fn split_list_values(value: &[u8]) -> Vec<&[u8]> {
+    let mut values = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut bracketed = false;
+
+    for (i, c) in value.iter().enumerate() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => quoted = false,
+                _ => (),
+            }
+            continue;
+        }
+
+        match c {
+            b'"' => quoted = true,
+            b'<' => bracketed = true,
+            b'>' => bracketed = false,
+            b',' if !bracketed => {
+                values.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    values.push(&value[start..]);
+    values
+        .into_iter()
+        .map(trim_spaces)
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+//Parses an unfolded header line, which yields one header per value for list headers
+fn parse_header_line(line: &[u8]) -> SipResult<Vec<(String, SipHeader)>> {
+    if let Some(colon) = line.iter().position(|c| *c == b':') {
+        let (name, value) = (&line[..colon], &line[colon + 1..]);
+        let values = split_list_values(value);
+
+        if is_list_header(&header_key(trim_spaces(name))) && !values.is_empty() {
+            return values
+                .into_iter()
+                .map(|v| parse_whole_header(&[name, b": ", v, b"\r\n"].concat()))
+                .collect();
+        }
+    }
+
+    Ok(vec![parse_whole_header(&[line, b"\r\n"].concat())?])
+}
+
+//Parses a header which must take the whole line, so `Max-Forwards: 70abc` isn't read as 70.
+//Headers which don't match their grammar are kept as `Unknown` with the whole value, except for
+//`Content-Length`, since the body can't be framed without it.
+fn parse_whole_header(line: &[u8]) -> SipResult<(String, SipHeader)> {
+    let error = match parse_sip_header(line) {
+        Ok((remaining, header)) => match trim_spaces(remaining) {
+            b"" | b"\r\n" => return Ok(header),
+            _ => MessageParserError::Parse {
+                detail: String::from("Unexpected data after header value"),
+                remaining: to_str_default(remaining),
+            },
+        },
+        Err(e) => e.into(),
+    };
+
+    let colon = match line.iter().position(|c| *c == b':') {
+        Some(colon) => colon,
+        None => return Err(error),
+    };
+    let name = trim_spaces(&line[..colon]);
+
+    if header_key(name) == b"content-length" {
+        return Err(error);
+    }
+
+    let value = &line[colon + 1..];
+    let value = value.strip_suffix(b"\r\n").unwrap_or(value);
+
+    Ok((
+        to_str_default(name),
+        SipHeader::Unknown {
+            name: to_str_default(expand_compact_name_bytes(name)),
+            value: to_str_default(trim_spaces(value)),
+        },
+    ))
+}
+
+//Raw text of a header line, including any folded continuation, and the headers parsed from it
+type HeaderLine<'a> = (&'a [u8], Vec<(String, SipHeader)>);
+
+fn parse_sip_headers(data: &[u8]) -> SipResult<Vec<HeaderLine<'_>>> {
+    split_header_lines(data)
+        .into_iter()
+        .map(|raw| Ok((raw, parse_header_line(&unfold_line(raw))?)))
+        .collect()
+}
 
 pub fn just_test() {
     //    test_message();
@@ -539,14 +1094,14 @@ fn test_messages() {
     use std::fs::File;
 
     let f = File::open("test_data/messages.log").expect("Failed to open messages.log file");
-    let mut parser = MessageParser::new(f);
+    let parser = MessageParser::new(f);
 
     let mut last_msg = None;
 
-    loop {
-        match parser.get_next() {
+    for res in parser {
+        match res {
             Err(e) => {
-                println!("Failed: {0:?}. Last msg:\r\n{1:?}", e, last_msg.unwrap());
+                println!("Failed: {0:?}. Last msg:\r\n{1:?}", e, last_msg);
                 break;
             }
             Ok(msg) => last_msg = Some(msg),
@@ -591,10 +1146,9 @@ fn test_parse(data: &[u8]) {
     let res = parse_sip_message(data);
     match res {
         Ok((_remaining, _msg)) => {
-            if let SipMethod::Unknown {
-                method: _,
-                uri: _,
-                version: _,
+            if let SipMethod::Request {
+                method: Method::Unknown(_),
+                ..
             } = &_msg.method
             {
                 println!(
@@ -663,3 +1217,919 @@ fn test_message() {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_keep_repeated() {
+        let data: &[u8] = b"SIP/2.0 200 OK\r\n\
+              Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n\
+              Contact: <sip:alice@10.0.0.1:5060>\r\n\
+              Via: SIP/2.0/UDP 10.0.0.2:5060;branch=z9hG4bK-2\r\n\
+              Contact: <sip:alice@10.0.0.2:5060>\r\n\
+              Via: SIP/2.0/TCP 10.0.0.3:5060;branch=z9hG4bK-3\r\n\
+              \r\n";
+        let headers = MessageParser::new(data).next_message().unwrap().headers;
+
+        assert_eq!(headers.len(), 5);
+        assert_eq!(headers.get_all("Via").count(), 3);
+        assert_eq!(headers.get_all("Contact").count(), 2);
+
+        if let Some(SipHeader::Via(via)) = headers.last("Via") {
+            assert_eq!(via.transport(), "TCP");
+        } else {
+            panic!("Via header not found");
+        }
+    }
+
+    const TWO_MESSAGES: &[u8] = b"OPTIONS sip:bob@10.0.0.2 SIP/2.0\r\n\
+        Call-ID: first\r\n\
+        CSeq: 1 OPTIONS\r\n\
+        \r\n\
+        SIP/2.0 200 OK\r\n\
+        Call-ID: second\r\n\
+        CSeq: 1 OPTIONS\r\n\
+        \r\n";
+
+    #[test]
+    fn parser_next_message() {
+        let mut parser = MessageParser::new(TWO_MESSAGES);
+
+        let first = parser.next_message().unwrap();
+        assert_eq!(
+            first.headers.first("Call-ID"),
+            Some(&SipHeader::CallID("first".to_owned()))
+        );
+
+        let second = parser.next_message().unwrap();
+        assert_eq!(
+            second.method,
+            SipMethod::Response {
+                version: "SIP/2.0".to_owned(),
+                code: 200,
+                reason: "OK".to_owned(),
+            }
+        );
+
+        match parser.next_message() {
+            Err(MessageParserError::EOF) => (),
+            r => panic!("Expected EOF, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parser_iterator() {
+        let msgs = MessageParser::new(TWO_MESSAGES).collect::<Vec<_>>();
+
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs.iter().all(|m| m.is_ok()));
+    }
+
+    #[test]
+    fn parser_iterator_reports_truncated() {
+        let data = &TWO_MESSAGES[..TWO_MESSAGES.len() - 10];
+        let mut parser = MessageParser::new(data);
+
+        assert!(parser.next().unwrap().is_ok());
+        match parser.next() {
+            Some(Err(MessageParserError::UnexpectedEof)) => (),
+            r => panic!("Expected unexpected EOF, got {:?}", r),
+        }
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn parser_body_by_content_length() {
+        let data: &[u8] = b"INVITE sip:bob@10.0.0.2 SIP/2.0\r\n\
+            Content-Type: application/sdp\r\n\
+            Content-Length: 20\r\n\
+            \r\n\
+            v=0\r\n\
+            \r\n\
+            s=-\r\n\
+            \r\n\
+            \x00\x01\r\n\r\n\
+            SIP/2.0 100 Trying\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            SIP/2.0 180 Ringing\r\n\
+            Call-ID: ringing\r\n\
+            \r\n";
+        let mut parser = MessageParser::new(data);
+
+        let invite = parser.next_message().unwrap();
+        assert_eq!(invite.content_length(), Some(20));
+        assert_eq!(
+            invite.body,
+            b"v=0\r\n\r\ns=-\r\n\r\n\x00\x01\r\n\r\n".to_vec()
+        );
+
+        let trying = parser.next_message().unwrap();
+        assert_eq!(trying.content_length(), Some(0));
+        assert!(trying.body.is_empty());
+
+        let ringing = parser.next_message().unwrap();
+        assert_eq!(ringing.content_length(), None);
+        assert!(ringing.body.is_empty());
+    }
+
+    //A message whose body looks like another one, followed by a real message
+    fn smuggled(max_forwards: &str, content_length: &str) -> String {
+        format!(
+            "MESSAGE sip:bob@10.0.0.2 SIP/2.0\r\n\
+             Max-Forwards: {}\r\n\
+             Content-Length: {}\r\n\
+             \r\n\
+             BYE sip:evil@x SIP/2.0\r\n\r\n\
+             OPTIONS sip:bob@10.0.0.2 SIP/2.0\r\n\
+             \r\n",
+            max_forwards, content_length
+        )
+    }
+
+    #[test]
+    fn parser_iterator_stops_on_error() {
+        let data = smuggled("70", "26abc");
+        let mut parser = MessageParser::new(data.as_bytes());
+
+        assert!(parser.next().unwrap().is_err());
+        assert!(parser.next().is_none());
+
+        //An invalid header which doesn't frame the body keeps the stream in sync
+        let data = smuggled("x", "26");
+        let msgs = MessageParser::new(data.as_bytes())
+            .map(|m| m.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].body, b"BYE sip:evil@x SIP/2.0\r\n\r\n".to_vec());
+        assert_eq!(msgs[1].method.method(), Some(&Method::Options));
+    }
+
+    #[test]
+    fn parser_truncated_body() {
+        let data: &[u8] = b"MESSAGE sip:bob@10.0.0.2 SIP/2.0\r\n\
+            Content-Length: 10\r\n\
+            \r\n\
+            hello";
+        let mut parser = MessageParser::new(data);
+
+        match parser.next_message() {
+            Err(MessageParserError::UnexpectedEof) => (),
+            r => panic!("Expected unexpected EOF, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parser_invalid_content_length() {
+        for len in &["4294967296", "", "-1", "0x10"] {
+            let data = format!("MESSAGE sip:bob@10.0.0.2 SIP/2.0\r\nl: {}\r\n\r\n", len);
+
+            match MessageParser::new(data.as_bytes()).next_message() {
+                Err(MessageParserError::Parse { .. }) => (),
+                r => panic!("Expected parse error for {:?}, got {:?}", len, r),
+            }
+        }
+
+        let data: &[u8] = b"MESSAGE sip:bob@10.0.0.2 SIP/2.0\r\nContent-Length: 4294967295\r\n\r\n";
+        match MessageParser::new(data).next_message() {
+            Err(MessageParserError::MessageTooLarge { .. }) => (),
+            r => panic!("Expected message too large, got {:?}", r),
+        }
+    }
+
+    fn big_invite(vias: usize, body_len: usize) -> Vec<u8> {
+        let mut data = b"INVITE sip:bob@10.0.0.2 SIP/2.0\r\n".to_vec();
+
+        for i in 0..vias {
+            data.extend_from_slice(
+                format!(
+                    "Via: SIP/2.0/UDP 10.0.0.{}:5060;branch=z9hG4bK-{}\r\n",
+                    i, i
+                )
+                .as_bytes(),
+            );
+        }
+
+        data.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body_len).as_bytes());
+        data.extend(std::iter::repeat_n(b'a', body_len));
+        data
+    }
+
+    #[test]
+    fn parser_big_message() {
+        let data = big_invite(70, 4000);
+        let mut parser = MessageParser::new(&data[..]);
+
+        let msg = parser.next_message().unwrap();
+        assert_eq!(msg.headers.get_all("Via").count(), 70);
+        assert_eq!(msg.body.len(), 4000);
+    }
+
+    #[test]
+    fn parser_message_too_large() {
+        let data = big_invite(70, 0);
+        let mut parser = MessageParser::with_max_message_size(&data[..], 1024);
+
+        match parser.next_message() {
+            Err(MessageParserError::MessageTooLarge { limit: 1024 }) => (),
+            r => panic!("Expected message too large, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parser_body_too_large() {
+        let data = big_invite(1, 2000);
+        let mut parser = MessageParser::with_max_message_size(&data[..], 1024);
+
+        match parser.next_message() {
+            Err(MessageParserError::MessageTooLarge { limit: 1024 }) => (),
+            r => panic!("Expected message too large, got {:?}", r),
+        }
+    }
+
+    const INVITE: &[u8] = b"INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+        Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds;rport\r\n\
+        Max-Forwards: 70\r\n\
+        To: \"Bob\" <sip:bob@biloxi.example.com>\r\n\
+        From: \"Alice\" <sip:alice@atlanta.example.com>;tag=1928301774\r\n\
+        Call-ID: a84b4c76e66710@pc33.atlanta.example.com\r\n\
+        CSeq: 314159 INVITE\r\n\
+        Contact: <sip:alice@10.0.0.1:5060;transport=udp>\r\n\
+        Allow: INVITE, ACK, CANCEL, BYE\r\n\
+        X-Custom: some value\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 12\r\n\
+        \r\n\
+        v=0\r\n\
+        s=-\r\n\r\n";
+
+    #[test]
+    fn encode_round_trip() {
+        let msg = MessageParser::new(INVITE).next_message().unwrap();
+
+        assert_eq!(msg.to_bytes(), INVITE.to_vec());
+        assert_eq!(msg.to_string().as_bytes(), INVITE);
+    }
+
+    #[test]
+    fn encode_modified() {
+        let mut msg = MessageParser::new(INVITE).next_message().unwrap();
+
+        if let Some(SipHeader::MaxForwards(n)) = msg.headers.first_mut("Max-Forwards") {
+            *n -= 1;
+        }
+        msg.set_body(b"v=0\r\n".to_vec());
+
+        let encoded = msg.to_bytes();
+        let reparsed = MessageParser::new(&encoded[..]).next_message().unwrap();
+
+        assert_eq!(reparsed, msg);
+        assert_eq!(
+            reparsed.headers.first("Max-Forwards"),
+            Some(&SipHeader::MaxForwards(69))
+        );
+        assert_eq!(reparsed.content_length(), Some(5));
+        assert_eq!(reparsed.body, b"v=0\r\n".to_vec());
+    }
+
+    #[test]
+    fn encode_preserving_raw() {
+        let data: &[u8] = b"SIP/2.0 200 OK\r\n\
+            Via:SIP/2.0/UDP 10.0.0.1:5060;rport;branch=z9hG4bK-1\r\n\
+            to:   <sip:bob@biloxi.example.com>;tag=abc\r\n\
+            Max-Forwards:  70\r\n\
+            X-Custom:   keep   this\r\n\
+            \r\n";
+
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let mut msg = parser.next_message().unwrap();
+
+        assert_eq!(msg.to_bytes(), data.to_vec());
+
+        if let Some(SipHeader::MaxForwards(n)) = msg.headers.first_mut("Max-Forwards") {
+            *n -= 1;
+        }
+
+        assert_eq!(
+            msg.to_string(),
+            "SIP/2.0 200 OK\r\n\
+             Via:SIP/2.0/UDP 10.0.0.1:5060;rport;branch=z9hG4bK-1\r\n\
+             to:   <sip:bob@biloxi.example.com>;tag=abc\r\n\
+             Max-Forwards: 69\r\n\
+             X-Custom:   keep   this\r\n\
+             \r\n"
+        );
+    }
+
+    const COMPACT: &[u8] = b"MESSAGE sip:bob@biloxi.example.com SIP/2.0\r\n\
+        v: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n\
+        f: <sip:alice@atlanta.example.com>;tag=1928301774\r\n\
+        t: <sip:bob@biloxi.example.com>\r\n\
+        i: a84b4c76e66710\r\n\
+        CSeq: 1 MESSAGE\r\n\
+        m: <sip:alice@10.0.0.1:5060>\r\n\
+        k: path, gruu\r\n\
+        o: presence\r\n\
+        u: presence, dialog\r\n\
+        s: Lunch\r\n\
+        c: text/plain\r\n\
+        l: 5\r\n\
+        \r\n\
+        hello";
+
+    #[test]
+    fn compact_headers() {
+        let msg = MessageParser::new(COMPACT).next_message().unwrap();
+
+        assert_eq!(msg.body, b"hello".to_vec());
+        assert_eq!(
+            msg.headers.first("Call-ID"),
+            Some(&SipHeader::CallID("a84b4c76e66710".to_owned()))
+        );
+        assert_eq!(msg.headers.first("i"), msg.headers.first("Call-ID"));
+        assert_eq!(
+            msg.headers.first("Supported"),
+            Some(&SipHeader::Supported(vec![
+                "path".to_owned(),
+                "gruu".to_owned()
+            ]))
+        );
+        assert_eq!(
+            msg.headers.first("Event"),
+            Some(&SipHeader::Event {
+                value: "presence".to_owned(),
+                params: Params::new(),
+            })
+        );
+        assert_eq!(
+            msg.headers.first("Subject"),
+            Some(&SipHeader::Unknown {
+                name: "Subject".to_owned(),
+                value: "Lunch".to_owned()
+            })
+        );
+        assert!(msg.headers.first("Via").is_some());
+        assert!(msg.headers.first("From").is_some());
+        assert!(msg.headers.first("To").is_some());
+        assert!(msg.headers.first("Contact").is_some());
+        assert!(msg.headers.first("Allow-Events").is_some());
+        assert_eq!(msg.content_length(), Some(5));
+
+        //Compact spelling is kept when encoding
+        assert_eq!(msg.to_bytes(), COMPACT.to_vec());
+    }
+
+    #[test]
+    fn encode_compact() {
+        let mut msg = MessageParser::new(INVITE).next_message().unwrap();
+        msg.headers.set(SipHeader::Unknown {
+            name: "Subject".to_owned(),
+            value: "Hi".to_owned(),
+        });
+
+        let mut encoded = vec![];
+        msg.encode_compact(&mut encoded).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        assert!(encoded.contains("\r\nv: SIP/2.0/UDP 10.0.0.1:5060"));
+        assert!(encoded.contains("\r\ni: a84b4c76e66710@pc33.atlanta.example.com\r\n"));
+        assert!(encoded.contains("\r\ns: Hi\r\n"));
+        assert!(encoded.contains("\r\nl: 12\r\n"));
+        assert!(encoded.contains("\r\nMax-Forwards: 70\r\n"));
+        assert!(encoded.contains("\r\nCSeq: 314159 INVITE\r\n"));
+
+        let reparsed = MessageParser::new(encoded.as_bytes())
+            .next_message()
+            .unwrap();
+        assert_eq!(reparsed.headers.len(), msg.headers.len());
+        assert_eq!(reparsed.body, msg.body);
+    }
+
+    #[test]
+    fn case_insensitive_headers() {
+        let data: &[u8] = b"MESSAGE sip:bob@biloxi.example.com SIP/2.0\r\n\
+            VIA: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n\
+            V: SIP/2.0/UDP 10.0.0.2:5060;branch=z9hG4bK-2\r\n\
+            call-id: a84b4c76e66710\r\n\
+            cSeq: 1 MESSAGE\r\n\
+            x-custom: value\r\n\
+            Max-Forwards : 70\r\n\
+            CONTENT-LENGTH: 5\r\n\
+            \r\n\
+            hello";
+
+        let mut msg = MessageParser::new(data).next_message().unwrap();
+
+        assert_eq!(msg.body, b"hello".to_vec());
+        assert_eq!(msg.content_length(), Some(5));
+        assert_eq!(msg.headers.get_all("Via").count(), 2);
+        assert_eq!(msg.headers.get_all("v").count(), 2);
+        assert_eq!(
+            msg.headers.first("CALL-ID"),
+            Some(&SipHeader::CallID("a84b4c76e66710".to_owned()))
+        );
+        assert_eq!(
+            msg.headers.first("CSeq"),
+            Some(&SipHeader::CSeq {
+                seq: 1,
+                method: Method::Message
+            })
+        );
+        assert_eq!(
+            msg.headers.first("Max-Forwards"),
+            Some(&SipHeader::MaxForwards(70))
+        );
+        assert!(msg.headers.contains("X-Custom"));
+
+        msg.set_body(b"bye".to_vec());
+        assert_eq!(msg.headers.get_all("Content-Length").count(), 1);
+
+        //Original spelling is kept when encoding
+        let encoded = msg.to_string();
+        assert!(encoded.contains("\r\nVIA: SIP/2.0/UDP 10.0.0.1:5060"));
+        assert!(encoded.contains("\r\ncall-id: a84b4c76e66710\r\n"));
+        assert!(encoded.contains("\r\nCONTENT-LENGTH: 3\r\n"));
+    }
+
+    #[test]
+    fn unfold() {
+        assert_eq!(
+            unfold_line(b"Subject: I know you're there, \r\n   pick up!"),
+            b"Subject: I know you're there, pick up!".to_vec()
+        );
+        assert_eq!(
+            unfold_line(b"Max-Forwards:\r\n\t70"),
+            b"Max-Forwards: 70".to_vec()
+        );
+    }
+
+    #[test]
+    fn folded_headers() {
+        let cases: Vec<(&[u8], SipHeader)> = vec![
+            (
+                b"Via: SIP/2.0/UDP 10.0.0.1:5060\r\n ;branch=z9hG4bK-1",
+                SipHeader::Via(
+                    parse_via(b"SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n")
+                        .unwrap()
+                        .1,
+                ),
+            ),
+            (
+                b"Contact:\r\n <sip:alice@10.0.0.1>",
+                SipHeader::Contact(parse_contact(b"<sip:alice@10.0.0.1>\r\n").unwrap().1),
+            ),
+            (
+                b"To: \"Bob\"\r\n\t<sip:bob@biloxi.example.com>",
+                SipHeader::To(
+                    parse_name_addr(b"\"Bob\" <sip:bob@biloxi.example.com>\r\n")
+                        .unwrap()
+                        .1,
+                ),
+            ),
+            (
+                b"From: <sip:alice@atlanta.example.com>\r\n ;tag=1928",
+                SipHeader::From(
+                    parse_name_addr(b"<sip:alice@atlanta.example.com>;tag=1928\r\n")
+                        .unwrap()
+                        .1,
+                ),
+            ),
+            (b"Expires:\r\n 3600", SipHeader::Expires(3600)),
+            (b"Max-Forwards:\r\n 70", SipHeader::MaxForwards(70)),
+            (b"Content-Length:\r\n 0", SipHeader::ContentLength(0)),
+            (
+                b"Min-SE:\r\n 90",
+                SipHeader::MinSE {
+                    value: 90,
+                    params: Params::new(),
+                },
+            ),
+            (
+                b"Call-ID:\r\n a84b4c76e66710",
+                SipHeader::CallID("a84b4c76e66710".to_owned()),
+            ),
+            (
+                b"CSeq: 1\r\n INVITE",
+                SipHeader::CSeq {
+                    seq: 1,
+                    method: Method::Invite,
+                },
+            ),
+            (
+                b"Accept:\r\n application/sdp",
+                SipHeader::Accept {
+                    value: "application/sdp".to_owned(),
+                    params: Params::new(),
+                },
+            ),
+            (
+                b"User-Agent: Zoiper\r\n for Windows",
+                SipHeader::UserAgent("Zoiper for Windows".to_owned()),
+            ),
+            (
+                b"Event:\r\n presence",
+                SipHeader::Event {
+                    value: "presence".to_owned(),
+                    params: Params::new(),
+                },
+            ),
+            (
+                b"Allow: INVITE,\r\n ACK",
+                SipHeader::Allow(vec!["INVITE".to_owned(), "ACK".to_owned()]),
+            ),
+            (
+                b"Allow-Events: presence,\r\n kpml",
+                SipHeader::AllowEvents(vec!["presence".to_owned(), "kpml".to_owned()]),
+            ),
+            (
+                b"Supported: replaces,\r\n timer",
+                SipHeader::Supported(vec!["replaces".to_owned(), "timer".to_owned()]),
+            ),
+            (
+                b"Require: 100rel,\r\n timer",
+                SipHeader::Require(vec!["100rel".to_owned(), "timer".to_owned()]),
+            ),
+            (
+                b"Authorization: Digest username=\"bob\",\r\n realm=\"biloxi\", nonce=\"abc\",\r\n\turi=\"sip:biloxi\", response=\"0a1b\"",
+                SipHeader::Authorization(Credentials::Digest(DigestCredentials {
+                    username: "bob".to_owned(),
+                    realm: "biloxi".to_owned(),
+                    nonce: "abc".to_owned(),
+                    uri: "sip:biloxi".to_owned(),
+                    response: "0a1b".to_owned(),
+                    ..Default::default()
+                })),
+            ),
+            (
+                b"WWW-Authenticate: Digest realm=\"biloxi\",\r\n nonce=\"abc\"",
+                SipHeader::WWWAuthenticate(Challenge::Digest(DigestChallenge {
+                    realm: "biloxi".to_owned(),
+                    nonce: "abc".to_owned(),
+                    ..Default::default()
+                })),
+            ),
+            (
+                b"Proxy-Authenticate: Bearer realm=\"SIP Service\",\r\n scope=\"abc def\"",
+                SipHeader::ProxyAuthenticate(Challenge::Other {
+                    scheme: "Bearer".to_owned(),
+                    params: vec![
+                        Param::new("realm", Some("SIP Service")),
+                        Param::new("scope", Some("abc def")),
+                    ]
+                    .into_iter()
+                    .collect(),
+                }),
+            ),
+            (
+                b"Session-ID:\r\n ab30317f1a784dc48ff824d0d3715d86",
+                SipHeader::SessionID("ab30317f1a784dc48ff824d0d3715d86".to_owned()),
+            ),
+            (
+                b"Server: Asterisk\r\n PBX",
+                SipHeader::Server("Asterisk PBX".to_owned()),
+            ),
+            (
+                b"Date: Sat, 13 Nov 2010\r\n 23:29:00 GMT",
+                SipHeader::Date("Sat, 13 Nov 2010 23:29:00 GMT".to_owned()),
+            ),
+            (
+                b"Content-Type:\r\n application/sdp",
+                SipHeader::ContentType("application/sdp".to_owned()),
+            ),
+            (
+                b"Session-Expires: 1800\r\n ;refresher=uac",
+                SipHeader::SessionExpires {
+                    value: 1800,
+                    params: parse_params(b";refresher=uac\r\n").unwrap().1,
+                },
+            ),
+            (
+                b"Accept-Language: en,\r\n pt",
+                SipHeader::AcceptLanguage("en, pt".to_owned()),
+            ),
+            (
+                b"X-Custom: first\r\n\t second",
+                SipHeader::Unknown {
+                    name: "X-Custom".to_owned(),
+                    value: "first second".to_owned(),
+                },
+            ),
+        ];
+
+        for (line, expected) in cases {
+            let mut data = line.to_vec();
+            data.extend_from_slice(b"\r\n\r\n");
+
+            let headers = parse_sip_headers(&data).unwrap();
+
+            assert_eq!(headers.len(), 1, "{}", to_str_dbg(line));
+            assert_eq!(headers[0].0, line);
+            assert_eq!(headers[0].1.len(), 1, "{}", to_str_dbg(line));
+            assert_eq!(headers[0].1[0].1, expected, "{}", to_str_dbg(line));
+        }
+    }
+
+    #[test]
+    fn folded_headers_in_message() {
+        let data: &[u8] = b"OPTIONS sip:bob@biloxi.example.com SIP/2.0\r\n\
+            Via: SIP/2.0/UDP 10.0.0.1:5060\r\n ;branch=z9hG4bK-1\r\n\
+            Call-ID:\r\n\t a84b4c76e66710\r\n\
+            Content-Length:\r\n 0\r\n\
+            \r\n";
+
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let msg = parser.next_message().unwrap();
+
+        assert_eq!(msg.headers.len(), 3);
+        assert_eq!(
+            msg.headers.first("Call-ID"),
+            Some(&SipHeader::CallID("a84b4c76e66710".to_owned()))
+        );
+        assert_eq!(msg.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn trailing_header_data() {
+        assert_eq!(
+            parse_header_line(b"Max-Forwards: 70 ").unwrap(),
+            vec![("Max-Forwards".to_owned(), SipHeader::MaxForwards(70))]
+        );
+
+        //Invalid values are kept as they are instead of being read partially
+        let invalid: &[(&[u8], &str, &str)] = &[
+            (b"Max-Forwards: 70abc", "Max-Forwards", "70abc"),
+            (
+                b"Contact: <sip:a@10.0.0.1> x",
+                "Contact",
+                "<sip:a@10.0.0.1> x",
+            ),
+            (
+                b"m: sip:a@10.0.0.1:99999",
+                "Contact",
+                "sip:a@10.0.0.1:99999",
+            ),
+            (
+                b"Via: SIP/2.0/UDP 10.0.0.1:70000;branch=z9hG4bK1",
+                "Via",
+                "SIP/2.0/UDP 10.0.0.1:70000;branch=z9hG4bK1",
+            ),
+            (
+                b"Allow-Events: presence ;foo",
+                "Allow-Events",
+                "presence ;foo",
+            ),
+        ];
+
+        for (line, name, value) in invalid.iter() {
+            let headers = parse_header_line(line).unwrap();
+            assert_eq!(
+                headers[0].1,
+                SipHeader::Unknown {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                },
+                "{}",
+                to_str_default(line)
+            );
+        }
+
+        let contacts = parse_header_line(b"Contact: <sip:a@10.0.0.1> x, <sip:b@10.0.0.2>").unwrap();
+        assert_eq!(contacts.len(), 2);
+        assert!(matches!(contacts[1].1, SipHeader::Contact(_)));
+
+        assert!(parse_header_line(b"Content-Length: 10abc").is_err());
+    }
+
+    #[test]
+    fn header_params() {
+        let (_, params) = parse_params(b";id=93809824\r\n").unwrap();
+        assert_eq!(
+            parse_header_line(b"Event: refer;id=93809824").unwrap()[0].1,
+            SipHeader::Event {
+                value: "refer".to_owned(),
+                params,
+            }
+        );
+
+        let (_, params) = parse_params(b";level=1\r\n").unwrap();
+        assert_eq!(
+            parse_header_line(b"Accept: application/sdp;level=1").unwrap()[0].1,
+            SipHeader::Accept {
+                value: "application/sdp".to_owned(),
+                params,
+            }
+        );
+
+        let (_, params) = parse_params(b";lr\r\n").unwrap();
+        assert_eq!(
+            parse_header_line(b"Min-SE: 90;lr").unwrap()[0].1,
+            SipHeader::MinSE { value: 90, params }
+        );
+
+        let data: &[u8] = b"SUBSCRIBE sip:bob@biloxi.example.com SIP/2.0\r\n\
+            Event: presence;id=123\r\n\
+            Accept: application/sdp;level=1\r\n\
+            Min-SE: 90;lr\r\n\
+            Allow-Events: presence ;foo\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+        let msg = MessageParser::new(data).next_message().unwrap();
+
+        match msg.headers.first("Event") {
+            Some(SipHeader::Event { value, params }) => {
+                assert_eq!(value, "presence");
+                assert_eq!(params.get("id"), Some("123"));
+            }
+            h => panic!("unexpected Event {:?}", h),
+        }
+        assert_eq!(msg.to_string().as_bytes(), data);
+    }
+
+    #[test]
+    fn split_values() {
+        assert_eq!(
+            split_list_values(b" a , b,c ,, "),
+            vec![b"a" as &[u8], b"b", b"c"]
+        );
+        assert_eq!(
+            split_list_values(b"\"Doe, \\\"J\\\"\" <sip:a@b;x=1,2>;q=0.5, <sip:c@d>"),
+            vec![
+                b"\"Doe, \\\"J\\\"\" <sip:a@b;x=1,2>;q=0.5" as &[u8],
+                b"<sip:c@d>"
+            ]
+        );
+    }
+
+    #[test]
+    fn list_headers() {
+        let data: &[u8] = b"SIP/2.0 200 OK\r\n\
+            Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1, SIP/2.0/TCP 10.0.0.2:5070\r\n\
+            Via: SIP/2.0/UDP 10.0.0.3:5060\r\n\
+            Contact: \"Doe, John\" <sip:john@10.0.0.1>;expires=60, <sip:john@10.0.0.2>\r\n\
+            Accept: application/sdp, text/plain\r\n\
+            Allow: INVITE, ACK\r\n\
+            X-Custom: a, b\r\n\
+            \r\n";
+
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let mut msg = parser.next_message().unwrap();
+
+        let vias = msg.headers.get_all("Via").collect::<Vec<_>>();
+        assert_eq!(vias.len(), 3);
+        assert_eq!(
+            vias[1],
+            &SipHeader::Via(Via::new("TCP", Host::from("10.0.0.2"), Some(5070)))
+        );
+
+        let contacts = msg.headers.get_all("Contact").collect::<Vec<_>>();
+        assert_eq!(
+            contacts,
+            vec![
+                &SipHeader::Contact(
+                    parse_contact(b"\"Doe, John\" <sip:john@10.0.0.1>;expires=60\r\n")
+                        .unwrap()
+                        .1
+                ),
+                &SipHeader::Contact(parse_contact(b"<sip:john@10.0.0.2>\r\n").unwrap().1),
+            ]
+        );
+
+        assert_eq!(
+            msg.headers.get_all("Accept").collect::<Vec<_>>(),
+            vec![
+                &SipHeader::Accept {
+                    value: "application/sdp".to_owned(),
+                    params: Params::new(),
+                },
+                &SipHeader::Accept {
+                    value: "text/plain".to_owned(),
+                    params: Params::new(),
+                },
+            ]
+        );
+        assert_eq!(msg.headers.get_all("Allow").count(), 1);
+        assert_eq!(msg.headers.get_all("X-Custom").count(), 1);
+
+        //Unmodified lists are written back on a single line
+        assert_eq!(msg.to_bytes(), data.to_vec());
+
+        if let Some(SipHeader::Accept { value, .. }) = msg.headers.first_mut("Accept") {
+            *value = "application/json".to_owned();
+        }
+
+        let encoded = msg.to_string();
+        assert!(encoded.contains("\r\nAccept: application/json\r\nAccept: text/plain\r\n"));
+        assert!(encoded.contains(
+            "\r\nVia: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1, SIP/2.0/TCP 10.0.0.2:5070\r\n"
+        ));
+    }
+
+    #[test]
+    fn absolute_uri_headers() {
+        let data: &[u8] = b"INVITE sip:bob@biloxi.example.com SIP/2.0\r\n\
+            Call-Info: <http://wwww.example.com/alice/photo.jpg> ;purpose=icon,\r\n \
+            <http://www.example.com/alice/> ;purpose=info\r\n\
+            Alert-Info: <http://www.example.com/sounds/moo.wav>\r\n\
+            Geolocation: <cid:target123@atlanta.example.com>;inserted-by=alice\r\n\
+            P-Asserted-Identity: \"Cullen Jennings\" <sip:fluffy@cisco.com>, <tel:+14085264000>\r\n\
+            \r\n";
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let msg = parser.next_message().unwrap();
+
+        let infos = msg.headers.get_all("Call-Info").collect::<Vec<_>>();
+        assert_eq!(infos.len(), 2);
+        match infos[0] {
+            SipHeader::CallInfo(info) => {
+                let uri = info.uri().as_absolute().unwrap();
+                assert_eq!(uri.scheme(), "http");
+                assert_eq!(
+                    info.uri().to_string(),
+                    "http://wwww.example.com/alice/photo.jpg"
+                );
+                assert_eq!(info.params().get("purpose"), Some("icon"));
+            }
+            h => panic!("unexpected Call-Info {:?}", h),
+        }
+
+        match msg.headers.first("Geolocation") {
+            Some(SipHeader::Geolocation(location)) => {
+                assert_eq!(location.uri().scheme(), "cid");
+                assert_eq!(location.params().get("inserted-by"), Some("alice"));
+            }
+            h => panic!("unexpected Geolocation {:?}", h),
+        }
+
+        assert!(matches!(
+            msg.headers.first("Alert-Info"),
+            Some(SipHeader::AlertInfo(_))
+        ));
+        assert_eq!(msg.headers.get_all("P-Asserted-Identity").count(), 2);
+        assert_eq!(msg.to_bytes(), data.to_vec());
+    }
+
+    #[test]
+    fn request_line_methods() {
+        let (_, start) = parse_sip_method(b"PRACK sip:bob@192.0.2.4 SIP/2.0\r\n").unwrap();
+
+        assert!(start.is_request());
+        assert_eq!(start.method(), Some(&Method::Prack));
+        assert_eq!(start.to_string(), "PRACK sip:bob@192.0.2.4 SIP/2.0");
+
+        let (_, start) = parse_sip_method(b"X-PING sip:bob@192.0.2.4 SIP/2.0\r\n").unwrap();
+        assert_eq!(start.method(), Some(&Method::Unknown("X-PING".to_owned())));
+
+        let (_, cseq) = parse_sip_header(b"CSeq: 4711 NOTIFY\r\n").unwrap();
+        assert_eq!(
+            cseq,
+            (
+                "CSeq".to_owned(),
+                SipHeader::CSeq {
+                    seq: 4711,
+                    method: Method::Notify
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn encode_response_line() {
+        let (_, method) = parse_sip_method(b"SIP/2.0 486 Busy Here\r\n").unwrap();
+        assert_eq!(method.to_string(), "SIP/2.0 486 Busy Here");
+    }
+
+    #[test]
+    fn ipv6_message() {
+        let data: &[u8] = b"INVITE sip:bob@[2001:db8::2]:5060 SIP/2.0\r\n\
+            Via: SIP/2.0/UDP [2001:db8::1]:5060;branch=z9hG4bK-1;received=2001:db8::1\r\n\
+            Contact: <sip:alice@[2001:db8::1]:5060;transport=udp>\r\n\
+            \r\n";
+        let msg = MessageParser::new(data).next_message().unwrap();
+
+        match msg.headers.first("Via") {
+            Some(SipHeader::Via(via)) => {
+                assert_eq!(via.host(), &Host::Ipv6("2001:db8::1".parse().unwrap()));
+                assert_eq!(via.port(), Some(5060));
+                assert_eq!(via.received(), Some("2001:db8::1".parse().unwrap()));
+            }
+            h => panic!("unexpected Via {:?}", h),
+        }
+
+        match msg.headers.first("Contact") {
+            Some(SipHeader::Contact(contact)) => {
+                let uri = contact.address().unwrap().uri().as_sip().unwrap();
+                assert_eq!(uri.host(), &Host::from("[2001:db8::1]"));
+                assert_eq!(uri.port(), Some(5060));
+            }
+            h => panic!("unexpected Contact {:?}", h),
+        }
+
+        assert_eq!(msg.to_string().as_bytes(), data);
+    }
+}
@@ -0,0 +1,543 @@
+use super::*;
+use std::fmt;
+
+/// Address of `From`, `To` and `Contact` headers (RFC 3261 section 25.1), either in the
+/// name-addr form (`"Alice" <sip:alice@atlanta.com>;tag=1`) or the addr-spec form
+/// (`sip:alice@atlanta.com;tag=1`). Params after the URI belong to the header.
+#[derive(PartialEq, Debug, Clone)]
+pub struct NameAddr {
+    display_name: Option<String>,
+    uri: URI,
+    params: Params,
+    bracketed: bool,
+}
+
+impl NameAddr {
+    pub fn new(display_name: Option<&str>, uri: URI) -> NameAddr {
+        NameAddr {
+            display_name: display_name.map(|n| n.to_owned()),
+            uri,
+            params: Params::new(),
+            bracketed: true,
+        }
+    }
+
+    /// Display name, already unquoted and unescaped.
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_ref().map(|n| n.as_ref())
+    }
+
+    pub fn set_display_name(&mut self, display_name: Option<&str>) {
+        self.display_name = display_name.map(|n| n.to_owned());
+    }
+
+    pub fn uri(&self) -> &URI {
+        &self.uri
+    }
+
+    pub fn uri_mut(&mut self) -> &mut URI {
+        &mut self.uri
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Params {
+        &mut self.params
+    }
+
+    /// Whether the address was given in the name-addr form, with the URI inside angle brackets.
+    pub fn is_name_addr(&self) -> bool {
+        self.bracketed || self.display_name.is_some()
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.params.get("tag")
+    }
+
+    pub fn set_tag(&mut self, tag: &str) {
+        self.params.set("tag", Some(tag));
+    }
+}
+
+impl fmt::Display for NameAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = &self.display_name {
+            write!(f, "\"{}\" ", escape_quoted(name))?;
+        }
+
+        //Always use the angle brackets, so URI params are never mistaken by header params
+        write!(f, "<{}>", self.uri)?;
+        write!(f, "{}", self.params)
+    }
+}
+
+/// A single `Contact` value, which may also be the `*` wildcard used to remove all bindings.
+//The wildcard is rare and never stored in bulk, so the address is not boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(PartialEq, Debug, Clone)]
+pub enum Contact {
+    Wildcard,
+    Address(NameAddr),
+}
+
+impl Contact {
+    pub fn is_wildcard(&self) -> bool {
+        *self == Contact::Wildcard
+    }
+
+    pub fn address(&self) -> Option<&NameAddr> {
+        match self {
+            Contact::Address(addr) => Some(addr),
+            Contact::Wildcard => None,
+        }
+    }
+
+    pub fn address_mut(&mut self) -> Option<&mut NameAddr> {
+        match self {
+            Contact::Address(addr) => Some(addr),
+            Contact::Wildcard => None,
+        }
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.address().and_then(|a| a.params().get(name))
+    }
+
+    pub fn expires(&self) -> Option<u32> {
+        self.param("expires").and_then(|e| e.parse().ok())
+    }
+
+    /// Preference among the contacts, from 0 to 1.
+    pub fn q(&self) -> Option<f32> {
+        self.param("q").and_then(|q| q.parse().ok())
+    }
+
+    /// Instance ID of the device (RFC 5626), like
+    /// `<urn:uuid:00000000-0000-1000-8000-AABBCCDDEEFF>`.
+    pub fn sip_instance(&self) -> Option<&str> {
+        self.param("+sip.instance")
+    }
+
+    /// Registration flow ID (RFC 5626).
+    pub fn reg_id(&self) -> Option<u32> {
+        self.param("reg-id").and_then(|r| r.parse().ok())
+    }
+}
+
+impl fmt::Display for Contact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Contact::Wildcard => write!(f, "*"),
+            Contact::Address(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+named!(
+    parse_display_name<Option<String>>,
+    alt_complete!(
+        //Either get the quoted string
+        map!(parse_quoted_string, Some) |
+        //Or tokens and spaces followed by a LT, so an addr-spec isn't read as a name
+        map!(
+            terminated!(
+                take_while!(|c| is_token_char(c) || nom::is_space(c)),
+                peek!(tag!("<"))
+            ),
+            |name| to_str(trim_spaces(name))
+        )
+    )
+);
+
+named!(
+    pub parse_name_addr<NameAddr>,
+    do_parse!(
+        take_while!(nom::is_space)
+            >> display_name: opt!(parse_display_name)
+            >> take_while!(nom::is_space)
+            >> uri: alt!(
+                map!(parse_uri_with_params, |uri| (uri, true)) |
+                map!(parse_uri_wo_params, |uri| (uri, false))
+            )
+            >> params: call!(parse_params)
+            >> (NameAddr {
+                display_name: display_name.flatten(),
+                uri: uri.0,
+                params,
+                bracketed: uri.1,
+            })
+    )
+);
+
+named!(
+    pub parse_contact<Contact>,
+    alt_complete!(
+        value!(
+            Contact::Wildcard,
+            delimited!(take_while!(nom::is_space), tag!("*"), take_while!(nom::is_space))
+        ) |
+        map!(parse_name_addr, Contact::Address)
+    )
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(scheme: &str, user: Option<&str>, host: &str, port: Option<u16>, params: Params) -> URI {
+        let mut uri = SipURI::new(scheme, user, Host::from(host));
+        uri.set_port(port);
+        *uri.params_mut() = params;
+        URI::Sip(uri)
+    }
+
+    fn tel(number: &str, params: Params) -> URI {
+        let mut uri = TelURI::new(number);
+        *uri.params_mut() = params;
+        URI::Tel(uri)
+    }
+
+    //Contact tests
+    #[test]
+    fn contact_full() {
+        assert_eq!(
+            parse_name_addr(
+                b"\"Alice Mark\" <sip:9989898919@127.0.0.1:35436;transport=UDP>;tag=asdasdasdasd;some=nice\r\n"
+            ),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: Some("Alice Mark".to_owned()),
+                    uri: uri(
+                        "sip",
+                        Some("9989898919"),
+                        "127.0.0.1",
+                        Some(35436),
+                        to_params(&["transport=UDP"]),
+                    ),
+                    params: to_params(&["tag=asdasdasdasd", "some=nice"]),
+                    bracketed: true,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_alias() {
+        assert_eq!(
+            parse_name_addr(b"sip:85999684700@localhost\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sip", Some("85999684700"), "localhost", None, Params::new()),
+                    params: Params::new(),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_host() {
+        assert_eq!(
+            parse_name_addr(b"tel:+5585999680047\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: tel("+5585999680047", Params::new()),
+                    params: Params::new(),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_host_with_port() {
+        assert_eq!(
+            parse_name_addr(b"sips:mark@localhost:3342\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sips", Some("mark"), "localhost", Some(3342), Params::new()),
+                    params: Params::new(),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_alias_empty() {
+        assert_eq!(
+            parse_name_addr(b"<sip:8882@127.0.0.1>\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sip", Some("8882"), "127.0.0.1", None, Params::new()),
+                    params: Params::new(),
+                    bracketed: true,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_with_params() {
+        assert_eq!(
+            parse_name_addr(b"sip:admin@localhost;tag=38298391\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sip", Some("admin"), "localhost", None, Params::new()),
+                    params: to_params(&["tag=38298391"]),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_uri_params() {
+        assert_eq!(
+            parse_name_addr(b"\"Alisson Bae\" <sip:asd@dsds:33;transport=333>;tag=aasdasd\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: Some("Alisson Bae".to_owned()),
+                    uri: uri(
+                        "sip",
+                        Some("asd"),
+                        "dsds",
+                        Some(33),
+                        to_params(&["transport=333"])
+                    ),
+                    params: to_params(&["tag=aasdasd"]),
+                    bracketed: true,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_uri_params_only() {
+        assert_eq!(
+            parse_name_addr(b"<sip:ddd@aaa:1111;transport=UDP>\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri(
+                        "sip",
+                        Some("ddd"),
+                        "aaa",
+                        Some(1111),
+                        to_params(&["transport=UDP"])
+                    ),
+                    params: Params::new(),
+                    bracketed: true,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_alias_with_params() {
+        assert_eq!(
+            parse_name_addr(b"sip:afonso@lage;tag=d2d2\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sip", Some("afonso"), "lage", None, Params::new()),
+                    params: to_params(&["tag=d2d2"]),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_alias_w_port_n_params() {
+        assert_eq!(
+            parse_name_addr(b"sip:afonso@lage:443;tag=d2d2\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: uri("sip", Some("afonso"), "lage", Some(443), Params::new()),
+                    params: to_params(&["tag=d2d2"]),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_alias_no_host_w_params() {
+        assert_eq!(
+            parse_name_addr(b"tel:+5585999680047;tag=d2d2\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: tel("+5585999680047", Params::new()),
+                    params: to_params(&["tag=d2d2"]),
+                    bracketed: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contact_no_alias_no_port_w_uri_params() {
+        assert_eq!(
+            parse_name_addr(b"<tel:190;type=emergency>\r\n"),
+            Ok((
+                b"\r\n" as &[u8],
+                NameAddr {
+                    display_name: None,
+                    uri: tel("190", to_params(&["type=emergency"])),
+                    params: Params::new(),
+                    bracketed: true,
+                }
+            ))
+        );
+    }
+
+    //Display tests
+    #[test]
+    fn contact_display() {
+        let (_, contact) = parse_name_addr(
+            b"\"Alice Mark\" <sip:9989898919@127.0.0.1:35436;transport=UDP>;tag=asd;some=nice\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            contact.to_string(),
+            "\"Alice Mark\" <sip:9989898919@127.0.0.1:35436;transport=UDP>;tag=asd;some=nice"
+        );
+    }
+
+    #[test]
+    fn contact_params() {
+        let (_, mut contact) =
+            parse_name_addr(b"<sip:ddd@aaa:1111;transport=UDP>;tag=abc;expires=60\r\n").unwrap();
+
+        assert_eq!(contact.params().get("TAG"), Some("abc"));
+        assert_eq!(
+            contact.uri().params().and_then(|p| p.get("transport")),
+            Some("UDP")
+        );
+
+        contact.params_mut().set("expires", Some("0"));
+        contact.params_mut().remove("tag");
+        assert_eq!(
+            contact.to_string(),
+            "<sip:ddd@aaa:1111;transport=UDP>;expires=0"
+        );
+    }
+
+    #[test]
+    fn contact_absolute_uri() {
+        let (_, info) =
+            parse_name_addr(b"<http://www.example.com/alice/photo.jpg> ;purpose=icon\r\n").unwrap();
+
+        assert_eq!(info.uri().scheme(), "http");
+        assert_eq!(info.params().get("purpose"), Some("icon"));
+        assert_eq!(
+            info.to_string(),
+            "<http://www.example.com/alice/photo.jpg>;purpose=icon"
+        );
+    }
+
+    #[test]
+    fn contact_display_adds_brackets() {
+        let (_, contact) = parse_name_addr(b"tel:+5585999680047;tag=d2d2\r\n").unwrap();
+        assert_eq!(contact.to_string(), "<tel:+5585999680047>;tag=d2d2");
+    }
+
+    #[test]
+    fn name_addr_display_name_quoting() {
+        let (_, addr) =
+            parse_name_addr(b"\"Doe, \\\"JD\\\" John\" <sip:john@example.com>;tag=8a1b\r\n")
+                .unwrap();
+
+        assert_eq!(addr.display_name(), Some("Doe, \"JD\" John"));
+        assert_eq!(addr.tag(), Some("8a1b"));
+        assert!(addr.is_name_addr());
+        assert_eq!(
+            addr.to_string(),
+            "\"Doe, \\\"JD\\\" John\" <sip:john@example.com>;tag=8a1b"
+        );
+
+        let (_, addr) = parse_name_addr(b"Bob  Smith <sip:bob@biloxi.com>\r\n").unwrap();
+        assert_eq!(addr.display_name(), Some("Bob  Smith"));
+        assert_eq!(addr.tag(), None);
+    }
+
+    #[test]
+    fn name_addr_set_tag() {
+        let mut addr = NameAddr::new(None, parse_uri(b"sip:bob@biloxi.com\r\n").unwrap().1);
+        addr.set_tag("a6c85cf");
+        addr.set_display_name(Some("Bob"));
+
+        assert_eq!(addr.tag(), Some("a6c85cf"));
+        assert_eq!(addr.to_string(), "\"Bob\" <sip:bob@biloxi.com>;tag=a6c85cf");
+    }
+
+    #[test]
+    fn contact_wildcard() {
+        let (_, contact) = parse_contact(b" *\r\n").unwrap();
+
+        assert!(contact.is_wildcard());
+        assert_eq!(contact.address(), None);
+        assert_eq!(contact.expires(), None);
+        assert_eq!(contact.to_string(), "*");
+    }
+
+    #[test]
+    fn contact_registration_params() {
+        let (_, contact) = parse_contact(
+            b"<sip:alice@192.0.2.4;transport=tcp>;expires=3600;q=0.7;+sip.instance=\"<urn:uuid:00000000-0000-1000-8000-AABBCCDDEEFF>\";reg-id=1\r\n",
+        )
+        .unwrap();
+
+        assert!(!contact.is_wildcard());
+        assert_eq!(contact.expires(), Some(3600));
+        assert_eq!(contact.q(), Some(0.7));
+        assert_eq!(
+            contact.sip_instance(),
+            Some("<urn:uuid:00000000-0000-1000-8000-AABBCCDDEEFF>")
+        );
+        assert_eq!(contact.reg_id(), Some(1));
+    }
+
+    #[test]
+    fn contact_addr_spec_with_quoted_param() {
+        let data: &[u8] = b"REGISTER sip:registrar.example.com SIP/2.0\r\n\
+            Contact: sip:alice@10.0.0.1;+sip.instance=\"<urn:uuid:1>\"\r\n\
+            \r\n";
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let msg = parser.next_message().unwrap();
+
+        match msg.headers.first("Contact") {
+            Some(SipHeader::Contact(contact)) => {
+                let addr = contact.address().unwrap();
+                assert_eq!(addr.display_name(), None);
+                assert_eq!(addr.uri().to_string(), "sip:alice@10.0.0.1");
+                assert_eq!(contact.sip_instance(), Some("<urn:uuid:1>"));
+            }
+            h => panic!("unexpected Contact {:?}", h),
+        }
+
+        assert_eq!(msg.to_bytes(), data.to_vec());
+    }
+}
@@ -0,0 +1,298 @@
+use super::*;
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+
+/// Characters allowed in param names and unquoted values. Includes the ones needed by hosts, like
+/// `:` and `[]` on IPv6 references.
+pub fn is_param_char(c: u8) -> bool {
+    nom::is_alphanumeric(c) || b"-_.!~*'()%+`[]/:&$".contains(&c)
+}
+
+/// Characters allowed in a token (RFC 3261 section 25.1).
+pub fn is_token_char(c: u8) -> bool {
+    nom::is_alphanumeric(c) || b"-.!%*_+`'~".contains(&c)
+}
+
+/// A single `name[=value]` parameter.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Param {
+    name: String,
+    value: Option<String>,
+    quoted: bool,
+}
+
+impl Param {
+    /// Creates a param, which will be written as a quoted string when the value has any char not
+    /// allowed in a token.
+    pub fn new(name: &str, value: Option<&str>) -> Param {
+        Param {
+            name: name.to_owned(),
+            value: value.map(|v| v.to_owned()),
+            quoted: value.is_some_and(|v| v.is_empty() || !v.bytes().all(is_param_char)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Value of the param, already unquoted. `None` for flag params, like `lr` or `rport`.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_ref().map(|v| v.as_ref())
+    }
+
+    pub fn is_quoted(&self) -> bool {
+        self.quoted
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        match &self.value {
+            Some(v) if self.quoted => write!(f, "=\"{}\"", escape_quoted(v)),
+            Some(v) => write!(f, "={}", v),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Ordered list of params, as found after `;` on URIs and headers. Lookups by name are
+/// case-insensitive.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Params {
+    params: Vec<Param>,
+}
+
+impl Params {
+    pub fn new() -> Params {
+        Params { params: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Param> {
+        self.params.iter()
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&Param> {
+        self.params
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Value of the given param. `None` when the param is missing or is a flag.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_param(name).and_then(|p| p.value())
+    }
+
+    /// Whether the param is present, either as a flag or with a value.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get_param(name).is_some()
+    }
+
+    /// Appends a param, even if there is another one with the same name.
+    pub fn push(&mut self, param: Param) {
+        self.params.push(param);
+    }
+
+    /// Sets the value of a param, replacing it in place or appending it when missing.
+    pub fn set(&mut self, name: &str, value: Option<&str>) {
+        let param = Param::new(name, value);
+
+        match self
+            .params
+            .iter_mut()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+        {
+            Some(p) => *p = param,
+            None => self.params.push(param),
+        }
+    }
+
+    /// Removes every param with the given name, returning the first one removed.
+    pub fn remove(&mut self, name: &str) -> Option<Param> {
+        let pos = self
+            .params
+            .iter()
+            .position(|p| p.name.eq_ignore_ascii_case(name))?;
+        let removed = self.params.remove(pos);
+
+        self.params.retain(|p| !p.name.eq_ignore_ascii_case(name));
+
+        Some(removed)
+    }
+}
+
+/// Writes the params in wire format, each one preceded by `;`.
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for p in &self.params {
+            write!(f, ";{}", p)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<Param> for Params {
+    fn from_iter<I: IntoIterator<Item = Param>>(iter: I) -> Self {
+        Params {
+            params: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Params {
+    type Item = &'a Param;
+    type IntoIter = slice::Iter<'a, Param>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.params.iter()
+    }
+}
+
+/// Escapes `"` and `\` so the value can be written inside a quoted string.
+pub fn escape_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Parses a quoted string, handling escaped chars, and returns its unescaped content.
+pub fn parse_quoted_string(input: &[u8]) -> IResult<&[u8], String> {
+    if input.first() != Some(&b'"') {
+        return Err(nom::Err::Error(error_position!(
+            input,
+            nom::ErrorKind::Custom(0)
+        )));
+    }
+
+    let mut value = vec![];
+    let mut escaped = false;
+
+    for (i, c) in input.iter().enumerate().skip(1) {
+        match c {
+            _ if escaped => {
+                value.push(*c);
+                escaped = false;
+            }
+            b'\\' => escaped = true,
+            b'"' => return Ok((&input[i + 1..], to_str_default(&value))),
+            _ => value.push(*c),
+        }
+    }
+
+    Err(nom::Err::Incomplete(nom::Needed::Unknown))
+}
+
+named!(
+    pub parse_param<Param>,
+    do_parse!(
+        name: take_while1!(is_param_char)
+            >> value: opt!(preceded!(tag!("="), alt_complete!(
+                map!(parse_quoted_string, |v| (v, true)) |
+                map!(take_while!(is_param_char), |v| (to_str_default(v), false))
+            )))
+            >> (Param {
+                name: to_str_default(name),
+                quoted: value.as_ref().is_some_and(|v| v.1),
+                value: value.map(|v| v.0),
+            })
+    )
+);
+
+//Separator between params, which may be surrounded by whitespace
+named!(
+    parse_semi<&[u8]>,
+    delimited!(
+        take_while!(nom::is_space),
+        tag!(";"),
+        take_while!(nom::is_space)
+    )
+);
+
+named!(
+    pub parse_params<Params>,
+    do_parse!(
+        params: many0!(preceded!(parse_semi, parse_param))
+        >> (params.into_iter().collect())
+    )
+);
+
+//Params from `name=value` or flag strings, shared by the tests of every module using params
+#[cfg(test)]
+pub fn to_params(list: &[&str]) -> Params {
+    list.iter()
+        .map(|p| {
+            let mut kv = p.splitn(2, '=');
+            Param::new(kv.next().unwrap(), kv.next())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_lookup() {
+        let (_, params) = parse_params(b";Branch=z9hG4bK-1;rport;received=10.0.0.1\r\n").unwrap();
+
+        assert_eq!(params.len(), 3);
+        assert_eq!(params.get("branch"), Some("z9hG4bK-1"));
+        assert_eq!(params.get("RPORT"), None);
+        assert!(params.contains("rport"));
+        assert!(!params.contains("maddr"));
+        assert_eq!(
+            params.to_string(),
+            ";Branch=z9hG4bK-1;rport;received=10.0.0.1"
+        );
+    }
+
+    #[test]
+    fn params_quoted() {
+        let (rest, params) =
+            parse_params(b";+sip.instance=\"<urn:uuid:f81d4fae>\";text=\"say \\\"hi\\\"\">\r\n")
+                .unwrap();
+
+        assert_eq!(rest, b">\r\n");
+        assert_eq!(params.get("+sip.instance"), Some("<urn:uuid:f81d4fae>"));
+        assert_eq!(params.get("text"), Some("say \"hi\""));
+        assert_eq!(
+            params.to_string(),
+            ";+sip.instance=\"<urn:uuid:f81d4fae>\";text=\"say \\\"hi\\\"\""
+        );
+    }
+
+    #[test]
+    fn params_ipv6_value() {
+        let (_, params) = parse_params(b";received=2001:db8::1;maddr=[::1]\r\n").unwrap();
+
+        assert_eq!(params.get("received"), Some("2001:db8::1"));
+        assert_eq!(params.get("maddr"), Some("[::1]"));
+    }
+
+    #[test]
+    fn params_mutation() {
+        let mut params = Params::new();
+        params.set("tag", Some("abc"));
+        params.set("lr", None);
+        params.set("TAG", Some("def"));
+        params.set("display", Some("Alice Mark"));
+
+        assert_eq!(params.to_string(), ";TAG=def;lr;display=\"Alice Mark\"");
+
+        assert_eq!(
+            params.remove("tag").map(|p| p.name().to_owned()),
+            Some("TAG".to_owned())
+        );
+        assert_eq!(params.remove("tag"), None);
+        assert_eq!(params.to_string(), ";lr;display=\"Alice Mark\"");
+    }
+}
@@ -0,0 +1,280 @@
+use super::*;
+use std::iter::FromIterator;
+use std::slice;
+
+/// Ordered set of routes (RFC 3261 section 12.2.1.1), as found on `Route` headers or built from
+/// `Record-Route` when a dialog is established.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct RouteSet {
+    routes: Vec<NameAddr>,
+}
+
+impl RouteSet {
+    pub fn new() -> RouteSet {
+        RouteSet { routes: vec![] }
+    }
+
+    /// The `Route` headers of a request, in the order they must be visited.
+    pub fn from_routes(headers: &SipHeaders) -> RouteSet {
+        headers
+            .get_all("Route")
+            .filter_map(|h| match h {
+                SipHeader::Route(addr) => Some(addr.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Route set of a dialog, built from the `Record-Route` headers of the request (by the UAS)
+    /// or of the response (by the UAC). The UAC visits them in the reverse order.
+    pub fn from_record_routes(headers: &SipHeaders, is_uac: bool) -> RouteSet {
+        let mut routes = headers
+            .get_all("Record-Route")
+            .filter_map(|h| match h {
+                SipHeader::RecordRoute(addr) => Some(addr.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if is_uac {
+            routes.reverse();
+        }
+
+        RouteSet { routes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, NameAddr> {
+        self.routes.iter()
+    }
+
+    /// Next hop of the route set.
+    pub fn first(&self) -> Option<&NameAddr> {
+        self.routes.first()
+    }
+
+    /// Adds a route to be visited before all the others.
+    pub fn push_front(&mut self, route: NameAddr) {
+        self.routes.insert(0, route);
+    }
+
+    pub fn push_back(&mut self, route: NameAddr) {
+        self.routes.push(route);
+    }
+
+    /// Removes the next hop, as a proxy does when the first route points to itself.
+    pub fn pop_front(&mut self) -> Option<NameAddr> {
+        if self.routes.is_empty() {
+            None
+        } else {
+            Some(self.routes.remove(0))
+        }
+    }
+
+    /// Whether the next hop is a loose router (its URI has the `lr` param). An empty route set
+    /// is loose, since the request goes straight to the remote target.
+    pub fn is_loose(&self) -> bool {
+        self.first()
+            .is_none_or(|r| r.uri().params().is_some_and(|p| p.contains("lr")))
+    }
+
+    /// Request-URI and `Route` headers of a request sent to `remote_target` within the dialog.
+    /// With a strict next hop, its URI becomes the Request-URI and the remote target is appended
+    /// as the last route.
+    pub fn request_target(&self, remote_target: &URI) -> (URI, RouteSet) {
+        if self.is_loose() {
+            return (remote_target.clone(), self.clone());
+        }
+
+        let mut routes = self.clone();
+        let first = routes.pop_front().map(|r| r.uri().clone());
+        routes.push_back(NameAddr::new(None, remote_target.clone()));
+
+        (first.unwrap_or_else(|| remote_target.clone()), routes)
+    }
+
+    /// Replaces the `Route` headers of a message by this route set. The routes are placed where
+    /// the first `Route` was or, when there was none, right after the `Via` headers.
+    pub fn set_routes(&self, headers: &mut SipHeaders) {
+        let pos = match headers.iter().position(|e| e.is("Route")) {
+            Some(pos) => pos,
+            None => headers
+                .iter()
+                .rposition(|e| e.is("Via"))
+                .map_or(headers.len(), |pos| pos + 1),
+        };
+
+        headers.remove_all("Route");
+
+        for (i, route) in self.routes.iter().enumerate() {
+            headers.insert(pos + i, "Route".to_owned(), SipHeader::Route(route.clone()));
+        }
+    }
+}
+
+impl FromIterator<NameAddr> for RouteSet {
+    fn from_iter<I: IntoIterator<Item = NameAddr>>(iter: I) -> Self {
+        RouteSet {
+            routes: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a RouteSet {
+    type Item = &'a NameAddr;
+    type IntoIter = slice::Iter<'a, NameAddr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &[u8] = b"SIP/2.0 200 OK\r\n\
+        Record-Route: <sip:p3.middle.com;lr>, <sip:p2.example.com;lr>\r\n\
+        Record-Route: <sip:p1.example.com;lr>\r\n\
+        Contact: <sip:bob@192.0.2.4>\r\n\
+        \r\n";
+
+    fn hosts(routes: &RouteSet) -> Vec<String> {
+        routes.iter().map(|r| r.uri().to_string()).collect()
+    }
+
+    #[test]
+    fn record_route_directions() {
+        let msg = MessageParser::new(RESPONSE).next_message().unwrap();
+
+        assert_eq!(
+            hosts(&RouteSet::from_record_routes(&msg.headers, false)),
+            vec![
+                "sip:p3.middle.com;lr",
+                "sip:p2.example.com;lr",
+                "sip:p1.example.com;lr"
+            ]
+        );
+        assert_eq!(
+            hosts(&RouteSet::from_record_routes(&msg.headers, true)),
+            vec![
+                "sip:p1.example.com;lr",
+                "sip:p2.example.com;lr",
+                "sip:p3.middle.com;lr"
+            ]
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let data: &[u8] = b"BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+            Route: <sip:p1.example.com;lr>,<sip:p2.example.com;lr>\r\n\
+            \r\n";
+        let mut msg = MessageParser::new(data).next_message().unwrap();
+        let mut routes = RouteSet::from_routes(&msg.headers);
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(
+            routes.pop_front().map(|r| r.uri().to_string()),
+            Some("sip:p1.example.com;lr".to_owned())
+        );
+
+        let (_, edge) = parse_name_addr(b"<sip:edge.example.com;lr>\r\n").unwrap();
+        routes.push_front(edge);
+        routes.set_routes(&mut msg.headers);
+
+        assert_eq!(
+            msg.to_string(),
+            "BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+             Route: <sip:edge.example.com;lr>\r\n\
+             Route: <sip:p2.example.com;lr>\r\n\
+             \r\n"
+        );
+    }
+
+    #[test]
+    fn set_routes_keeps_order() {
+        let data: &[u8] = b"BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+            Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n\
+            Max-Forwards: 70\r\n\
+            Route: <sip:p1.example.com;lr>\r\n\
+            Call-ID: a84b4c76e66710\r\n\
+            Route: <sip:p2.example.com;lr>\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+        let mut parser = MessageParser::new(data);
+        parser.set_preserve_raw_headers(true);
+        let mut msg = parser.next_message().unwrap();
+
+        let mut routes = RouteSet::from_routes(&msg.headers);
+        routes.pop_front();
+        routes.set_routes(&mut msg.headers);
+
+        assert_eq!(
+            msg.to_string(),
+            "BYE sip:bob@192.0.2.4 SIP/2.0\r\n\
+             Via: SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1\r\n\
+             Max-Forwards: 70\r\n\
+             Route: <sip:p2.example.com;lr>\r\n\
+             Call-ID: a84b4c76e66710\r\n\
+             Content-Type: text/plain\r\n\
+             Content-Length: 0\r\n\
+             \r\n"
+        );
+
+        //Without any Route, the new ones go right after the Via headers
+        msg.headers.remove_all("Route");
+        routes.set_routes(&mut msg.headers);
+
+        let names = msg.headers.iter().map(|e| e.name()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "Via",
+                "Route",
+                "Max-Forwards",
+                "Call-ID",
+                "Content-Type",
+                "Content-Length"
+            ]
+        );
+    }
+
+    #[test]
+    fn loose_and_strict_routing() {
+        let (_, target) = parse_uri(b"sip:bob@192.0.2.4\r\n").unwrap();
+        let route = |s: &str| parse_name_addr(format!("{}\r\n", s).as_bytes()).unwrap().1;
+
+        let loose: RouteSet = vec![route("<sip:p1.example.com;lr>")].into_iter().collect();
+        assert!(loose.is_loose());
+
+        let (uri, routes) = loose.request_target(&target);
+        assert_eq!(uri, target);
+        assert_eq!(routes, loose);
+
+        let strict: RouteSet = vec![
+            route("<sip:p1.example.com>"),
+            route("<sip:p2.example.com;lr>"),
+        ]
+        .into_iter()
+        .collect();
+        assert!(!strict.is_loose());
+
+        let (uri, routes) = strict.request_target(&target);
+        assert_eq!(uri.to_string(), "sip:p1.example.com");
+        assert_eq!(
+            hosts(&routes),
+            vec!["sip:p2.example.com;lr", "sip:bob@192.0.2.4"]
+        );
+
+        assert!(RouteSet::new().is_loose());
+    }
+}
@@ -0,0 +1,174 @@
+use super::*;
+use std::fmt;
+
+//Digits, hex digits of local numbers, and the visual separators allowed between them
+fn is_phone_char(c: u8) -> bool {
+    nom::is_hex_digit(c) || b"*#-.()+".contains(&c)
+}
+
+fn is_visual_separator(c: char) -> bool {
+    "-.()".contains(c)
+}
+
+/// Removes the visual separators of a number, leaving only its digits (and the leading `+` of
+/// global numbers). Hex digits are uppercased.
+pub fn strip_visual_separators(number: &str) -> String {
+    number
+        .chars()
+        .filter(|c| !is_visual_separator(*c))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// A `tel` URI (RFC 3966), like `tel:+1-201-555-0123` or `tel:7042;phone-context=example.com`.
+///
+/// The number is kept as written, with its visual separators, so it is written back unchanged.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TelURI {
+    number: String,
+    params: Params,
+}
+
+impl TelURI {
+    pub fn new(number: &str) -> TelURI {
+        TelURI {
+            number: number.to_owned(),
+            params: Params::new(),
+        }
+    }
+
+    /// The number as written, including visual separators.
+    pub fn number(&self) -> &str {
+        &self.number
+    }
+
+    /// The number without visual separators, as used for comparison.
+    pub fn digits(&self) -> String {
+        strip_visual_separators(&self.number)
+    }
+
+    /// Whether this is a global number (starting with `+`), which needs no phone context.
+    pub fn is_global(&self) -> bool {
+        self.number.starts_with('+')
+    }
+
+    /// Context of a local number, either a domain name or a global number prefix.
+    pub fn phone_context(&self) -> Option<&str> {
+        self.params.get("phone-context")
+    }
+
+    /// Extension, without visual separators.
+    pub fn ext(&self) -> Option<String> {
+        self.params.get("ext").map(strip_visual_separators)
+    }
+
+    /// ISDN subaddress.
+    pub fn isub(&self) -> Option<&str> {
+        self.params.get("isub")
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Params {
+        &mut self.params
+    }
+
+    /// Compares two tel URIs as RFC 3966 section 4 does: numbers are compared without visual
+    /// separators and both must have the same params, in any order.
+    pub fn matches(&self, other: &TelURI) -> bool {
+        let normalized_value = |p: &Param| {
+            p.value()
+                .map(|v| match p.name().to_ascii_lowercase().as_str() {
+                    "ext" => strip_visual_separators(v),
+                    "phone-context" if v.starts_with('+') => strip_visual_separators(v),
+                    _ => v.to_ascii_uppercase(),
+                })
+        };
+
+        let contains_all = |a: &Params, b: &Params| {
+            a.iter().all(|p| {
+                b.get_param(p.name())
+                    .is_some_and(|o| normalized_value(p) == normalized_value(o))
+            })
+        };
+
+        self.digits() == other.digits()
+            && self.params.len() == other.params.len()
+            && contains_all(&self.params, &other.params)
+            && contains_all(&other.params, &self.params)
+    }
+}
+
+impl fmt::Display for TelURI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tel:{}{}", self.number, self.params)
+    }
+}
+
+//Without params, for when they belong to the header instead
+named!(
+    pub parse_tel_uri_base<TelURI>,
+    do_parse!(
+        tag_no_case!("tel:")
+            >> number: take_while1!(is_phone_char)
+            >> (TelURI::new(&to_str_default(number)))
+    )
+);
+
+named!(
+    pub parse_tel_uri<TelURI>,
+    do_parse!(
+        uri: parse_tel_uri_base
+            >> params: parse_params
+            >> (TelURI { params, ..uri })
+    )
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tel(s: &str) -> TelURI {
+        parse_tel_uri(format!("{}\r\n", s).as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn tel_global() {
+        let t = tel("tel:+1-201-555-0123;ext=1(23)4");
+
+        assert!(t.is_global());
+        assert_eq!(t.number(), "+1-201-555-0123");
+        assert_eq!(t.digits(), "+12015550123");
+        assert_eq!(t.ext(), Some("1234".to_owned()));
+        assert_eq!(t.phone_context(), None);
+        assert_eq!(t.to_string(), "tel:+1-201-555-0123;ext=1(23)4");
+    }
+
+    #[test]
+    fn tel_local() {
+        let t = tel("tel:7042;phone-context=example.com;isub=1411");
+
+        assert!(!t.is_global());
+        assert_eq!(t.digits(), "7042");
+        assert_eq!(t.phone_context(), Some("example.com"));
+        assert_eq!(t.isub(), Some("1411"));
+
+        let t = tel("tel:*21#;phone-context=+1-212");
+        assert_eq!(t.digits(), "*21#");
+        assert_eq!(t.phone_context(), Some("+1-212"));
+    }
+
+    #[test]
+    fn tel_comparison() {
+        assert!(tel("tel:+1-201-555-0123").matches(&tel("tel:+1(201)5550123")));
+        assert!(tel("tel:7042;phone-context=Example.com;ext=22")
+            .matches(&tel("tel:7042;ext=2-2;phone-context=example.com")));
+        assert!(tel("tel:a7;phone-context=x").matches(&tel("tel:A7;phone-context=x")));
+
+        assert!(!tel("tel:+12015550123").matches(&tel("tel:12015550123")));
+        assert!(!tel("tel:+12015550123").matches(&tel("tel:+12015550123;ext=1")));
+        assert!(!tel("tel:7042;phone-context=a.com").matches(&tel("tel:7042;phone-context=b.com")));
+    }
+}
@@ -1,129 +1,51 @@
 use super::*;
-
-//TODO: Convert this to a tuple?
-pub type Params = Vec<String>;
-
-named!(
-    pub parse_params<Params>,
-    do_parse!(
-        params: opt!(preceded!(tag!(";"), many_till!(
-            do_parse!(
-                p: take_while!(call!(is_not_reserved_char_except, b"="))
-                    >> opt!(tag!(";"))
-                    >> (p)
-            ), peek!(one_of!("()<>@,:;\\/?= \t\r\n")))))
-        >> (params.unwrap_or_default().0.into_iter().filter_map(to_str).collect())
-    )
-);
+use std::fmt;
+use std::net::SocketAddr;
 
 #[derive(PartialEq, Debug)]
 pub struct SockAddr {
-    addr: String,
-    port: u32,
+    addr: Host,
+    port: u16,
 }
 
-named!(
-    pub parse_sock_addr<SockAddr>,
-    do_parse!(
-        s: take_until!(":")
-            >> tag!(":")
-            >> port: parse_u32
-            >> (SockAddr{addr: to_str_default(s), port})
-    )
-);
+impl SockAddr {
+    pub fn new(addr: Host, port: u16) -> SockAddr {
+        SockAddr { addr, port }
+    }
 
-#[derive(PartialEq, Debug)]
-pub struct URI {
-    protocol: String,
-    extension: String,
-    domain: Option<String>,
-    port: Option<u32>,
-    params: Params,
-}
+    pub fn addr(&self) -> &Host {
+        &self.addr
+    }
 
-named!(
-    pub parse_uri_with_params<URI>,
-    do_parse!(
-        tag!("<")
-            >> protocol: take_until_and_consume!(":")
-            >> extension: take_until_either!("@>;\r\n")
-            >> domain: opt!(preceded!(tag!("@"), take_until_either!(":>;\r\n")))
-            >> port: opt!(preceded!(tag!(":"), parse_u32))
-            >> params: call!(parse_params)
-            >> tag!(">")
-            >> (URI{
-                protocol: to_str_default(protocol),
-                extension: to_str_default(extension),
-                domain: domain.and_then(to_str),
-                port,
-                params,
-            })
-    )
-);
+    pub fn port(&self) -> u16 {
+        self.port
+    }
 
-named!(
-    pub parse_uri_wo_params<URI>,
-    do_parse!(
-        protocol: take_until_and_consume!(":")
-            >> extension: take_until_either!("@>;\r\n")
-            >> domain: opt!(preceded!(tag!("@"), take_till!(is_reserved_char)))
-            >> port: opt!(preceded!(tag!(":"), parse_u32))
-            >> (URI {
-                protocol: to_str_default(protocol),
-                extension: to_str_default(extension),
-                domain: domain.and_then(to_str),
-                port,
-                params: vec![],
-            })
-    )
-);
+    /// Converts to a `std::net` address, which is only possible when the host is an IP literal.
+    pub fn to_socket_addr(&self) -> Option<SocketAddr> {
+        self.addr.ip_addr().map(|ip| SocketAddr::new(ip, self.port))
+    }
+}
 
-named!(
-    pub parse_uri<URI>,
-    do_parse!(
-        opt!(tag!("<"))
-            >> protocol: take_until_and_consume!(":")
-            >> extension: take_until_either!("@>;\r\n")
-            >> domain: opt!(preceded!(tag!("@"), take_until_either!(":>;\r\n")))
-            >> port: opt!(preceded!(tag!(":"), parse_u32))
-            >> params: call!(parse_params)
-            >> opt!(tag!(">"))
-            >> (URI{
-                protocol: to_str_default(protocol),
-                extension: to_str_default(extension),
-                domain: domain.and_then(to_str),
-                port,
-                params,
-            })
-    )
-);
+impl From<SocketAddr> for SockAddr {
+    fn from(addr: SocketAddr) -> SockAddr {
+        SockAddr::new(addr.ip().into(), addr.port())
+    }
+}
 
-#[derive(PartialEq, Debug)]
-pub struct ContactInfo {
-    alias: Option<String>,
-    uri: URI,
-    params: Params,
+impl fmt::Display for SockAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.addr, self.port)
+    }
 }
 
 named!(
-    pub parse_contact<ContactInfo>,
+    pub parse_sock_addr<SockAddr>,
     do_parse!(
-            take_while_s!(nom::is_space)
-            >> alias: opt!(
-                alt_complete!(
-                    //Either get the quoted string
-                    delimited!(tag!("\""),take_until!("\""),tag!("\"")) |
-                    //Or until there is a LT
-                    take_until!("<") 
-                ))
-            >> take_while_s!(nom::is_space)
-            >> uri: alt!(call!(parse_uri_with_params) | call!(parse_uri_wo_params))
-            >> params: call!(parse_params)
-            >> (ContactInfo {
-                    alias: alias.and_then(to_str),
-                    uri,
-                    params,
-                })
+        addr: parse_host
+            >> tag!(":")
+            >> port: parse_port
+            >> (SockAddr{addr, port})
     )
 );
 
@@ -176,6 +98,7 @@ named!(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::Ipv4Addr;
 
     //SockAddr tests
     #[test]
@@ -185,7 +108,7 @@ mod tests {
             Ok((
                 b"\r\n" as &[u8],
                 SockAddr {
-                    addr: "192.168.0.1".to_owned(),
+                    addr: Host::Ipv4(Ipv4Addr::new(192, 168, 0, 1)),
                     port: 4444
                 }
             ))
@@ -199,22 +122,43 @@ mod tests {
             Ok((
                 b";tag=some-thing\r\n" as &[u8],
                 SockAddr {
-                    addr: "192.168.0.1".to_owned(),
+                    addr: Host::Ipv4(Ipv4Addr::new(192, 168, 0, 1)),
                     port: 4444
                 }
             ))
         );
     }
 
+    #[test]
+    fn sockaddr_ipv6() {
+        let (rest, addr) = parse_sock_addr(b"[2001:db8::1]:5060;branch=z9hG4bK-1\r\n").unwrap();
+
+        assert_eq!(rest, b";branch=z9hG4bK-1\r\n");
+        assert_eq!(addr.addr(), &Host::Ipv6("2001:db8::1".parse().unwrap()));
+        assert_eq!(addr.port(), 5060);
+        assert_eq!(addr.to_string(), "[2001:db8::1]:5060");
+        assert_eq!(
+            addr.to_socket_addr(),
+            Some("[2001:db8::1]:5060".parse().unwrap())
+        );
+        assert_eq!(SockAddr::from(addr.to_socket_addr().unwrap()), addr);
+    }
+
+    #[test]
+    fn sockaddr_domain() {
+        let (_, addr) = parse_sock_addr(b"proxy.example.com:5060\r\n").unwrap();
+
+        assert_eq!(addr.addr(), &Host::Domain("proxy.example.com".to_owned()));
+        assert_eq!(addr.to_socket_addr(), None);
+        assert!(parse_sock_addr(b"10.0.0.1:70000\r\n").is_err());
+    }
+
     //Params tests
     #[test]
     fn params() {
         assert_eq!(
             parse_params(b";tag=a;another=afonso\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                vec!["tag=a".to_owned(), "another=afonso".to_owned()]
-            ))
+            Ok((b"\r\n" as &[u8], to_params(&["tag=a", "another=afonso"])))
         );
     }
 
@@ -224,7 +168,7 @@ mod tests {
             parse_params(b";branch=z9hG4bK-d8754z-05751188cc710991-1---d8754z-\r\n"),
             Ok((
                 b"\r\n" as &[u8],
-                vec!["branch=z9hG4bK-d8754z-05751188cc710991-1---d8754z-".to_owned()]
+                to_params(&["branch=z9hG4bK-d8754z-05751188cc710991-1---d8754z-"])
             ))
         )
     }
@@ -301,258 +245,16 @@ mod tests {
         assert_eq!(parse_u32(b" 44\r\n"), Ok((b"\r\n" as &[u8], 44)));
     }
 
-    //Contact tests
+    //Display tests
     #[test]
-    fn contact_full() {
-        assert_eq!(
-            parse_contact(
-                b"\"Alice Mark\" <sip:9989898919@127.0.0.1:35436;transport=UDP>;tag=asdasdasdasd;some=nice\r\n"
-            ),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: Some("Alice Mark".to_owned()),
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "9989898919".to_owned(),
-                        domain: Some("127.0.0.1".to_owned()),
-                        port: Some(35436),
-                        params: vec!["transport=UDP".to_owned()]
-                    },
-                    params: vec!["tag=asdasdasdasd".to_owned(), "some=nice".to_owned()],
-                }
-            ))
-        );
+    fn sockaddr_display() {
+        let (_, addr) = parse_sock_addr(b"192.168.0.1:4444\r\n").unwrap();
+        assert_eq!(addr.to_string(), "192.168.0.1:4444");
     }
 
     #[test]
-    fn contact_no_alias() {
-        assert_eq!(
-            parse_contact(b"sip:85999684700@localhost\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "85999684700".to_owned(),
-                        domain: Some("localhost".to_owned()),
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_no_host() {
-        assert_eq!(
-            parse_contact(b"tel:+5585999680047\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "tel".to_owned(),
-                        extension: "+5585999680047".to_owned(),
-                        domain: None,
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_host_with_port() {
-        assert_eq!(
-            parse_contact(b"sips:mark@localhost:3342\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sips".to_owned(),
-                        extension: "mark".to_owned(),
-                        domain: Some("localhost".to_owned()),
-                        port: Some(3342),
-                        params: vec![],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_alias_empty() {
-        assert_eq!(
-            parse_contact(b"<sip:8882@127.0.0.1>\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "8882".to_owned(),
-                        domain: Some("127.0.0.1".to_owned()),
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_with_params() {
-        assert_eq!(
-            parse_contact(b"sip:admin@localhost;tag=38298391\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "admin".to_owned(),
-                        domain: Some("localhost".to_owned()),
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec!["tag=38298391".to_owned()],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_uri_params() {
-        assert_eq!(
-            parse_contact(b"\"Alisson Bae\" <sip:asd@dsds:33;transport=333>;tag=aasdasd\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: Some("Alisson Bae".to_owned()),
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "asd".to_owned(),
-                        domain: Some("dsds".to_owned()),
-                        port: Some(33),
-                        params: vec!["transport=333".to_owned()],
-                    },
-                    params: vec!["tag=aasdasd".to_owned()],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_uri_params_only() {
-        assert_eq!(
-            parse_contact(b"<sip:ddd@aaa:1111;transport=UDP>\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "ddd".to_owned(),
-                        domain: Some("aaa".to_owned()),
-                        port: Some(1111),
-                        params: vec!["transport=UDP".to_owned()],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_no_alias_with_params() {
-        assert_eq!(
-            parse_contact(b"sip:afonso@lage;tag=d2d2\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "afonso".to_owned(),
-                        domain: Some("lage".to_owned()),
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec!["tag=d2d2".to_owned()],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_no_alias_w_port_n_params() {
-        assert_eq!(
-            parse_contact(b"sip:afonso@lage:443;tag=d2d2\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "sip".to_owned(),
-                        extension: "afonso".to_owned(),
-                        domain: Some("lage".to_owned()),
-                        port: Some(443),
-                        params: vec![],
-                    },
-                    params: vec!["tag=d2d2".to_owned()],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_no_alias_no_host_w_params() {
-        assert_eq!(
-            parse_contact(b"tel:+5585999680047;tag=d2d2\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "tel".to_owned(),
-                        extension: "+5585999680047".to_owned(),
-                        domain: None,
-                        port: None,
-                        params: vec![],
-                    },
-                    params: vec!["tag=d2d2".to_owned()],
-                }
-            ))
-        );
-    }
-
-    #[test]
-    fn contact_no_alias_no_port_w_uri_params() {
-        assert_eq!(
-            parse_contact(b"<tel:190;type=emergency>\r\n"),
-            Ok((
-                b"\r\n" as &[u8],
-                ContactInfo {
-                    alias: None,
-                    uri: URI {
-                        protocol: "tel".to_owned(),
-                        extension: "190".to_owned(),
-                        domain: None,
-                        port: None,
-                        params: vec!["type=emergency".to_owned()],
-                    },
-                    params: vec![],
-                }
-            ))
-        );
+    fn uri_display() {
+        let (_, uri) = parse_uri(b"sip:bob@10.0.0.2:5060;transport=udp SIP/2.0\r\n").unwrap();
+        assert_eq!(uri.to_string(), "sip:bob@10.0.0.2:5060;transport=udp");
     }
 }
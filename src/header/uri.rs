@@ -0,0 +1,675 @@
+use super::*;
+use std::fmt;
+use std::net::SocketAddr;
+
+//Chars which may appear unescaped on each part of a SIP URI (RFC 3261 section 25.1)
+fn is_unreserved(c: u8) -> bool {
+    nom::is_alphanumeric(c) || b"-_.!~*'()".contains(&c)
+}
+
+fn is_user_char(c: u8) -> bool {
+    is_unreserved(c) || b"%&=+$,;?/".contains(&c)
+}
+
+fn is_password_char(c: u8) -> bool {
+    is_unreserved(c) || b"%&=+$,".contains(&c)
+}
+
+fn is_header_char(c: u8) -> bool {
+    is_unreserved(c) || b"%[]/?:+$".contains(&c)
+}
+
+fn is_scheme_char(c: u8) -> bool {
+    nom::is_alphanumeric(c) || b"+-.".contains(&c)
+}
+
+/// SIP or SIPS URI (RFC 3261 section 19.1).
+///
+/// User, password and headers are kept unescaped and are escaped again when written.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SipURI {
+    scheme: String,
+    user: Option<String>,
+    password: Option<String>,
+    host: Host,
+    port: Option<u16>,
+    params: Params,
+    headers: Vec<(String, String)>,
+}
+
+impl SipURI {
+    pub fn new(scheme: &str, user: Option<&str>, host: Host) -> SipURI {
+        SipURI {
+            scheme: scheme.to_owned(),
+            user: user.map(|u| u.to_owned()),
+            password: None,
+            host,
+            port: None,
+            params: Params::new(),
+            headers: vec![],
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Whether this is a `sips` URI.
+    pub fn is_secure(&self) -> bool {
+        self.scheme.eq_ignore_ascii_case("sips")
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_ref().map(|u| u.as_ref())
+    }
+
+    pub fn set_user(&mut self, user: Option<&str>) {
+        self.user = user.map(|u| u.to_owned());
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(|p| p.as_ref())
+    }
+
+    pub fn set_password(&mut self, password: Option<&str>) {
+        self.password = password.map(|p| p.to_owned());
+    }
+
+    pub fn host(&self) -> &Host {
+        &self.host
+    }
+
+    pub fn set_host(&mut self, host: Host) {
+        self.host = host;
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn set_port(&mut self, port: Option<u16>) {
+        self.port = port;
+    }
+
+    /// Socket address of the URI when its host is an IP literal, using `default_port` when the
+    /// URI has none (5060 for `sip`, 5061 for `sips`).
+    pub fn socket_addr(&self, default_port: u16) -> Option<SocketAddr> {
+        self.host
+            .ip_addr()
+            .map(|ip| SocketAddr::new(ip, self.port.unwrap_or(default_port)))
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Params {
+        &mut self.params
+    }
+
+    /// Headers given after `?`, as unescaped name and value pairs.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Vec<(String, String)> {
+        &mut self.headers
+    }
+
+    /// Compares two URIs using the rules from RFC 3261 section 19.1.4, where host, scheme and
+    /// param values are case-insensitive, params present on only one side are ignored (except
+    /// for `user`, `ttl`, `method`, `maddr` and `transport`) and headers must all match.
+    pub fn matches(&self, other: &SipURI) -> bool {
+        if !self.scheme.eq_ignore_ascii_case(&other.scheme)
+            || self.user != other.user
+            || self.password != other.password
+            || !self.host.matches(&other.host)
+            || self.port != other.port
+        {
+            return false;
+        }
+
+        let same_value = |a: &Param, b: &Param| match (a.value(), b.value()) {
+            (Some(a), Some(b)) => {
+                percent_decode(a.as_bytes()).eq_ignore_ascii_case(&percent_decode(b.as_bytes()))
+            }
+            (None, None) => true,
+            _ => false,
+        };
+
+        for name in &["user", "ttl", "method", "maddr", "transport"] {
+            match (self.params.get_param(name), other.params.get_param(name)) {
+                (None, None) => (),
+                (Some(a), Some(b)) if same_value(a, b) => (),
+                _ => return false,
+            }
+        }
+
+        for a in &self.params {
+            if let Some(b) = other.params.get_param(a.name()) {
+                if !same_value(a, b) {
+                    return false;
+                }
+            }
+        }
+
+        let has_header = |headers: &[(String, String)], (name, value): &(String, String)| {
+            headers
+                .iter()
+                .any(|(n, v)| n.eq_ignore_ascii_case(name) && v == value)
+        };
+
+        self.headers.len() == other.headers.len()
+            && self.headers.iter().all(|h| has_header(&other.headers, h))
+            && other.headers.iter().all(|h| has_header(&self.headers, h))
+    }
+}
+
+impl fmt::Display for SipURI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.scheme)?;
+
+        if let Some(user) = &self.user {
+            write!(f, "{}", percent_encode(user, is_user_char))?;
+
+            if let Some(password) = &self.password {
+                write!(f, ":{}", percent_encode(password, is_password_char))?;
+            }
+
+            write!(f, "@")?;
+        }
+
+        write!(f, "{}", self.host)?;
+
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+
+        write!(f, "{}", self.params)?;
+
+        for (i, (name, value)) in self.headers.iter().enumerate() {
+            write!(
+                f,
+                "{}{}={}",
+                if i == 0 { "?" } else { "&" },
+                percent_encode(name, is_header_char),
+                percent_encode(value, is_header_char)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// URI of any other scheme, like `urn:`, `mailto:` or `http:`, kept as it was received.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AbsoluteURI {
+    scheme: String,
+    opaque: String,
+}
+
+impl AbsoluteURI {
+    pub fn new(scheme: &str, opaque: &str) -> AbsoluteURI {
+        AbsoluteURI {
+            scheme: scheme.to_owned(),
+            opaque: opaque.to_owned(),
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Everything after the `:` of the scheme.
+    pub fn opaque(&self) -> &str {
+        &self.opaque
+    }
+}
+
+impl fmt::Display for AbsoluteURI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.opaque)
+    }
+}
+
+/// Any URI found on request lines and address headers.
+#[derive(PartialEq, Debug, Clone)]
+pub enum URI {
+    Sip(SipURI),
+    Tel(TelURI),
+    Absolute(AbsoluteURI),
+}
+
+impl URI {
+    pub fn scheme(&self) -> &str {
+        match self {
+            URI::Sip(uri) => uri.scheme(),
+            URI::Tel(_) => "tel",
+            URI::Absolute(uri) => uri.scheme(),
+        }
+    }
+
+    pub fn as_sip(&self) -> Option<&SipURI> {
+        match self {
+            URI::Sip(uri) => Some(uri),
+            _ => None,
+        }
+    }
+
+    pub fn as_tel(&self) -> Option<&TelURI> {
+        match self {
+            URI::Tel(uri) => Some(uri),
+            _ => None,
+        }
+    }
+
+    pub fn as_absolute(&self) -> Option<&AbsoluteURI> {
+        match self {
+            URI::Absolute(uri) => Some(uri),
+            _ => None,
+        }
+    }
+
+    /// URI params. `None` for absolute URIs, whose params are kept on the opaque part.
+    pub fn params(&self) -> Option<&Params> {
+        match self {
+            URI::Sip(uri) => Some(uri.params()),
+            URI::Tel(uri) => Some(uri.params()),
+            URI::Absolute(_) => None,
+        }
+    }
+
+    pub fn params_mut(&mut self) -> Option<&mut Params> {
+        match self {
+            URI::Sip(uri) => Some(uri.params_mut()),
+            URI::Tel(uri) => Some(uri.params_mut()),
+            URI::Absolute(_) => None,
+        }
+    }
+
+    /// Compares using the rules of each scheme. URIs of different schemes never match.
+    pub fn matches(&self, other: &URI) -> bool {
+        match (self, other) {
+            (URI::Sip(a), URI::Sip(b)) => a.matches(b),
+            (URI::Tel(a), URI::Tel(b)) => a.matches(b),
+            (URI::Absolute(a), URI::Absolute(b)) => {
+                a.scheme.eq_ignore_ascii_case(&b.scheme) && a.opaque == b.opaque
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<SipURI> for URI {
+    fn from(uri: SipURI) -> URI {
+        URI::Sip(uri)
+    }
+}
+
+impl From<TelURI> for URI {
+    fn from(uri: TelURI) -> URI {
+        URI::Tel(uri)
+    }
+}
+
+impl From<AbsoluteURI> for URI {
+    fn from(uri: AbsoluteURI) -> URI {
+        URI::Absolute(uri)
+    }
+}
+
+impl fmt::Display for URI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            URI::Sip(uri) => write!(f, "{}", uri),
+            URI::Tel(uri) => write!(f, "{}", uri),
+            URI::Absolute(uri) => write!(f, "{}", uri),
+        }
+    }
+}
+
+named!(
+    parse_userinfo<(String, Option<String>)>,
+    do_parse!(
+        user: take_while1!(is_user_char)
+            >> password: opt!(complete!(preceded!(tag!(":"), take_while!(is_password_char))))
+            >> tag!("@")
+            >> ((percent_decode(user), password.map(percent_decode)))
+    )
+);
+
+named!(
+    parse_uri_header<(String, String)>,
+    do_parse!(
+        name: take_while1!(is_header_char)
+            >> tag!("=")
+            >> value: take_while!(is_header_char)
+            >> ((percent_decode(name), percent_decode(value)))
+    )
+);
+
+named!(
+    parse_uri_headers<Vec<(String, String)>>,
+    preceded!(
+        tag!("?"),
+        separated_nonempty_list_complete!(tag!("&"), parse_uri_header)
+    )
+);
+
+//Everything up to the port, which is shared by all SIP URI forms
+named!(
+    pub parse_sip_uri_base<SipURI>,
+    do_parse!(
+        scheme: alt_complete!(tag_no_case!("sips") | tag_no_case!("sip"))
+            >> tag!(":")
+            >> userinfo: opt!(complete!(parse_userinfo))
+            >> host: parse_host
+            >> port: opt!(complete!(preceded!(tag!(":"), return_error!(parse_port))))
+            >> ({
+                let (user, password) = match userinfo {
+                    Some((user, password)) => (Some(user), password),
+                    None => (None, None),
+                };
+
+                SipURI {
+                    scheme: to_str_default(scheme),
+                    user,
+                    password,
+                    host,
+                    port,
+                    params: Params::new(),
+                    headers: vec![],
+                }
+            })
+    )
+);
+
+//SIP URI with its params and headers, as found inside angle brackets or on the request line
+named!(
+    pub parse_sip_uri<SipURI>,
+    do_parse!(
+        uri: parse_sip_uri_base
+            >> params: parse_params
+            >> headers: opt!(complete!(parse_uri_headers))
+            >> (SipURI {
+                params,
+                headers: headers.unwrap_or_default(),
+                ..uri
+            })
+    )
+);
+
+named!(
+    parse_absolute_uri<AbsoluteURI>,
+    do_parse!(
+        scheme: take_while1!(is_scheme_char)
+            >> tag!(":")
+            >> opaque: take_till1!(call!(is_any_of, b"<>\" \t\r\n"))
+            >> (AbsoluteURI::new(&to_str_default(scheme), &to_str_default(opaque)))
+    )
+);
+
+//Without the angle brackets, the opaque part ends on the first param or list separator
+named!(
+    parse_absolute_uri_base<AbsoluteURI>,
+    do_parse!(
+        scheme: take_while1!(is_scheme_char)
+            >> tag!(":")
+            >> opaque: take_till1!(call!(is_any_of, b"<>\";, \t\r\n"))
+            >> (AbsoluteURI::new(&to_str_default(scheme), &to_str_default(opaque)))
+    )
+);
+
+//URI with its params, as found inside angle brackets or on the request line
+named!(
+    parse_full_uri<URI>,
+    alt_complete!(
+        map!(parse_tel_uri, URI::Tel)
+            | map!(parse_sip_uri, URI::Sip)
+            | map!(parse_absolute_uri, URI::Absolute)
+    )
+);
+
+named!(
+    pub parse_uri_with_params<URI>,
+    delimited!(tag!("<"), parse_full_uri, tag!(">"))
+);
+
+//Without the angle brackets, any params after the URI belong to the header instead
+named!(
+    pub parse_uri_wo_params<URI>,
+    alt_complete!(
+        map!(parse_tel_uri_base, URI::Tel) |
+        map!(parse_sip_uri_base, URI::Sip) |
+        map!(parse_absolute_uri_base, URI::Absolute)
+    )
+);
+
+named!(
+    pub parse_uri<URI>,
+    alt_complete!(parse_uri_with_params | parse_full_uri)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn uri(s: &str) -> SipURI {
+        parse_sip_uri(format!("{}\r\n", s).as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn uri_full() {
+        let u = uri(
+            "sips:alice:secret%20word@atlanta.com:5061;transport=tcp;lr?Subject=Project%20X&Priority=urgent",
+        );
+
+        assert!(u.is_secure());
+        assert_eq!(u.user(), Some("alice"));
+        assert_eq!(u.password(), Some("secret word"));
+        assert_eq!(u.host(), &Host::Domain("atlanta.com".to_owned()));
+        assert_eq!(u.port(), Some(5061));
+        assert_eq!(u.params().get("transport"), Some("tcp"));
+        assert!(u.params().contains("lr"));
+        assert_eq!(
+            u.headers(),
+            &[
+                ("Subject".to_owned(), "Project X".to_owned()),
+                ("Priority".to_owned(), "urgent".to_owned())
+            ][..]
+        );
+        assert_eq!(
+            u.to_string(),
+            "sips:alice:secret%20word@atlanta.com:5061;transport=tcp;lr?Subject=Project%20X&Priority=urgent"
+        );
+    }
+
+    #[test]
+    fn uri_host_only() {
+        let u = uri("sip:example.com");
+
+        assert_eq!(u.user(), None);
+        assert_eq!(u.host(), &Host::Domain("example.com".to_owned()));
+        assert_eq!(u.port(), None);
+        assert_eq!(u.to_string(), "sip:example.com");
+
+        let u = uri("sip:10.0.0.1:5060;lr");
+        assert_eq!(u.host(), &Host::Ipv4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(u.port(), Some(5060));
+        assert!(u.params().contains("lr"));
+        assert_eq!(u.socket_addr(5061), Some("10.0.0.1:5060".parse().unwrap()));
+
+        assert_eq!(uri("sip:example.com").socket_addr(5060), None);
+        assert_eq!(
+            uri("sip:10.0.0.1").socket_addr(5060),
+            Some("10.0.0.1:5060".parse().unwrap())
+        );
+
+        //An invalid port fails the whole URI, instead of routing it to the default port
+        assert!(parse_uri(b"sip:10.0.0.1:65536\r\n").is_err());
+        assert!(parse_uri(b"<sip:a@10.0.0.1:99999>\r\n").is_err());
+        assert!(parse_sip_uri(b"sip:10.0.0.1:x\r\n").is_err());
+    }
+
+    #[test]
+    fn uri_escaped_user() {
+        let u = uri("sip:%61lice@atlanta.com");
+        assert_eq!(u.user(), Some("alice"));
+
+        let u = uri("sip:+1-212-555-1212;npdi@gateway.com;user=phone");
+        assert_eq!(u.user(), Some("+1-212-555-1212;npdi"));
+        assert_eq!(u.params().get("user"), Some("phone"));
+        assert_eq!(
+            u.to_string(),
+            "sip:+1-212-555-1212;npdi@gateway.com;user=phone"
+        );
+
+        let mut u = SipURI::new("sip", Some("j doe"), Host::from("example.com"));
+        u.set_port(Some(5070));
+        assert_eq!(u.to_string(), "sip:j%20doe@example.com:5070");
+    }
+
+    #[test]
+    fn uri_ipv6() {
+        let u = uri("sip:alice@[2001:db8::10]:5070;maddr=[2001:db8::1]");
+
+        assert_eq!(u.host(), &Host::Ipv6("2001:db8::10".parse().unwrap()));
+        assert_eq!(u.port(), Some(5070));
+        assert_eq!(u.params().get("maddr"), Some("[2001:db8::1]"));
+        assert_eq!(
+            u.to_string(),
+            "sip:alice@[2001:db8::10]:5070;maddr=[2001:db8::1]"
+        );
+
+        assert!(uri("sip:[2001:DB8:0::10]").matches(&uri("sip:[2001:db8::10]")));
+        assert_eq!(
+            u.socket_addr(5060),
+            Some("[2001:db8::10]:5070".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn uri_request_line() {
+        let (rest, u) = parse_uri(b"sip:bob@biloxi.com;maddr=10.0.0.1 SIP/2.0\r\n").unwrap();
+
+        assert_eq!(rest, b" SIP/2.0\r\n");
+        assert_eq!(u.params().and_then(|p| p.get("maddr")), Some("10.0.0.1"));
+
+        let (rest, u) = parse_uri(b"urn:service:sos SIP/2.0\r\n").unwrap();
+        assert_eq!(rest, b" SIP/2.0\r\n");
+        assert_eq!(u.to_string(), "urn:service:sos");
+    }
+
+    #[test]
+    fn uri_schemes() {
+        let (_, u) = parse_uri(b"<tel:+1-201-555-0123;ext=22>;tag=1\r\n").unwrap();
+        assert_eq!(u.scheme(), "tel");
+        assert_eq!(
+            u.as_tel().map(|t| t.digits()),
+            Some("+12015550123".to_owned())
+        );
+        assert_eq!(u.params().and_then(|p| p.get("ext")), Some("22"));
+
+        let (rest, u) = parse_uri_wo_params(b"tel:+1-201-555-0123;tag=1\r\n").unwrap();
+        assert_eq!(rest, b";tag=1\r\n");
+        assert_eq!(u.params(), Some(&Params::new()));
+
+        let (_, u) = parse_uri(b"sips:bob@biloxi.com\r\n").unwrap();
+        assert_eq!(u.scheme(), "sips");
+        assert!(u.as_sip().is_some());
+        assert!(!u.matches(&URI::Tel(TelURI::new("+12015550123"))));
+    }
+
+    #[test]
+    fn uri_absolute() {
+        let cases: &[(&[u8], &str, &str)] = &[
+            (
+                b"<urn:uuid:f81d4fae-7dec-11d0-a765-00a0c91e6bf6>",
+                "urn",
+                "uuid:f81d4fae-7dec-11d0-a765-00a0c91e6bf6",
+            ),
+            (
+                b"<http://www.example.com/alice/photo.jpg?size=2;x=y>",
+                "http",
+                "//www.example.com/alice/photo.jpg?size=2;x=y",
+            ),
+            (b"<mailto:alice@example.com>", "mailto", "alice@example.com"),
+            (b"<im:alice@example.com>", "im", "alice@example.com"),
+            (b"<pres:alice@example.com>", "pres", "alice@example.com"),
+            (
+                b"<cid:target123@atlanta.example.com>",
+                "cid",
+                "target123@atlanta.example.com",
+            ),
+        ];
+
+        for (data, scheme, opaque) in cases {
+            let (_, u) = parse_uri(&[data, &b"\r\n"[..]].concat()).unwrap();
+            let abs = u.as_absolute().unwrap();
+
+            assert_eq!(abs.scheme(), *scheme);
+            assert_eq!(abs.opaque(), *opaque);
+            assert_eq!(u.params(), None);
+            assert_eq!(format!("<{}>", u).as_bytes(), *data);
+        }
+
+        let (rest, u) = parse_uri_wo_params(b"urn:service:sos;purpose=x\r\n").unwrap();
+        assert_eq!(rest, b";purpose=x\r\n");
+        assert_eq!(u, URI::Absolute(AbsoluteURI::new("urn", "service:sos")));
+        assert!(u.matches(&URI::Absolute(AbsoluteURI::new("URN", "service:sos"))));
+        assert!(!u.matches(&URI::Absolute(AbsoluteURI::new("urn", "service:SOS"))));
+    }
+
+    #[test]
+    fn uri_comparison_equal() {
+        let equal = [
+            (
+                "sip:%61lice@atlanta.com;transport=TCP",
+                "sip:alice@AtLanTa.CoM;Transport=tcp",
+            ),
+            ("sip:carol@chicago.com", "sip:carol@chicago.com;newparam=5"),
+            ("sip:carol@chicago.com", "sip:carol@chicago.com;security=on"),
+            (
+                "sip:biloxi.com;transport=tcp;method=REGISTER?to=sip:bob%40biloxi.com",
+                "sip:biloxi.com;method=REGISTER;transport=tcp?to=sip:bob%40biloxi.com",
+            ),
+            (
+                "sip:alice@atlanta.com?subject=project%20x&priority=urgent",
+                "sip:alice@atlanta.com?priority=urgent&subject=project%20x",
+            ),
+        ];
+
+        for (a, b) in equal.iter() {
+            assert!(uri(a).matches(&uri(b)), "{} == {}", a, b);
+            assert!(uri(b).matches(&uri(a)), "{} == {}", b, a);
+        }
+    }
+
+    #[test]
+    fn uri_comparison_not_equal() {
+        let different = [
+            (
+                "SIP:ALICE@AtLanTa.CoM;Transport=udp",
+                "sip:alice@AtLanTa.CoM;Transport=UDP",
+            ),
+            ("sip:bob@biloxi.com", "sip:bob@biloxi.com:5060"),
+            ("sip:bob@biloxi.com", "sip:bob@biloxi.com;transport=udp"),
+            ("sip:bob@biloxi.com", "sips:bob@biloxi.com"),
+            (
+                "sip:carol@chicago.com;newparam=5",
+                "sip:carol@chicago.com;newparam=6",
+            ),
+            (
+                "sip:carol@chicago.com",
+                "sip:carol@chicago.com?Subject=next%20meeting",
+            ),
+            ("sip:bob@phone21.boxesbybob.com", "sip:bob@192.0.2.4"),
+        ];
+
+        for (a, b) in different.iter() {
+            assert!(!uri(a).matches(&uri(b)), "{} != {}", a, b);
+            assert!(!uri(b).matches(&uri(a)), "{} != {}", b, a);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::*;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Prefix of branches created by RFC 3261 compliant elements.
+pub const MAGIC_COOKIE: &str = "z9hG4bK";
+
+/// A single `Via` value (RFC 3261 section 20.42), like
+/// `SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Via {
+    protocol: String,
+    version: String,
+    transport: String,
+    host: Host,
+    port: Option<u16>,
+    params: Params,
+}
+
+impl Via {
+    /// Creates a `SIP/2.0` Via sent by the given host, without any params.
+    pub fn new(transport: &str, host: Host, port: Option<u16>) -> Via {
+        Via {
+            protocol: "SIP".to_owned(),
+            version: "2.0".to_owned(),
+            transport: transport.to_owned(),
+            host,
+            port,
+            params: Params::new(),
+        }
+    }
+
+    /// Protocol name, which is always `SIP` for SIP messages.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Transport, like `UDP`, `TCP`, `TLS` or `WS`.
+    pub fn transport(&self) -> &str {
+        &self.transport
+    }
+
+    pub fn host(&self) -> &Host {
+        &self.host
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Params {
+        &mut self.params
+    }
+
+    pub fn branch(&self) -> Option<&str> {
+        self.params.get("branch")
+    }
+
+    pub fn set_branch(&mut self, branch: &str) {
+        self.params.set("branch", Some(branch));
+    }
+
+    /// Whether the branch starts with the magic cookie, so it can be used to match transactions.
+    pub fn has_magic_cookie(&self) -> bool {
+        self.branch().is_some_and(|b| b.starts_with(MAGIC_COOKIE))
+    }
+
+    /// Address the request was received from, as added by the server. Unparseable values are
+    /// ignored.
+    pub fn received(&self) -> Option<IpAddr> {
+        self.params
+            .get("received")
+            .and_then(|r| r.trim_start_matches('[').trim_end_matches(']').parse().ok())
+    }
+
+    pub fn set_received(&mut self, ip: IpAddr) {
+        self.params.set("received", Some(&ip.to_string()));
+    }
+
+    /// The `rport` param (RFC 3581). `Some(None)` when the client asked for it with an empty
+    /// `rport` and `Some(Some(port))` when the server filled it.
+    pub fn rport(&self) -> Option<Option<u16>> {
+        self.params
+            .get_param("rport")
+            .map(|p| p.value().and_then(|v| v.parse().ok()))
+    }
+
+    pub fn set_rport(&mut self, port: Option<u16>) {
+        let port = port.map(|p| p.to_string());
+        self.params.set("rport", port.as_deref());
+    }
+
+    pub fn maddr(&self) -> Option<Host> {
+        self.params.get("maddr").map(Host::from)
+    }
+
+    pub fn ttl(&self) -> Option<u8> {
+        self.params.get("ttl").and_then(|t| t.parse().ok())
+    }
+}
+
+impl fmt::Display for Via {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{} {}",
+            self.protocol, self.version, self.transport, self.host
+        )?;
+
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+
+        write!(f, "{}", self.params)
+    }
+}
+
+//Slash between the sent-protocol parts, which may be surrounded by whitespace
+named!(
+    parse_slash<&[u8]>,
+    delimited!(
+        take_while!(nom::is_space),
+        tag!("/"),
+        take_while!(nom::is_space)
+    )
+);
+
+named!(
+    pub parse_via<Via>,
+    do_parse!(
+        take_while!(nom::is_space)
+            >> protocol: take_while1!(is_token_char)
+            >> parse_slash
+            >> version: take_while1!(is_token_char)
+            >> parse_slash
+            >> transport: take_while1!(is_token_char)
+            >> take_while1!(nom::is_space)
+            >> host: parse_host
+            >> port: opt!(complete!(preceded!(tag!(":"), return_error!(parse_port))))
+            >> params: parse_params
+            >> (Via {
+                protocol: to_str_default(protocol),
+                version: to_str_default(version),
+                transport: to_str_default(transport),
+                host,
+                port,
+                params,
+            })
+    )
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn via(s: &str) -> Via {
+        parse_via(format!("{}\r\n", s).as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn via_full() {
+        let v = via(
+            "SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK776asdhds;received=192.0.2.1;rport=5070;maddr=224.2.0.1;ttl=16",
+        );
+
+        assert_eq!(v.protocol(), "SIP");
+        assert_eq!(v.version(), "2.0");
+        assert_eq!(v.transport(), "UDP");
+        assert_eq!(v.host(), &Host::Ipv4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(v.port(), Some(5060));
+        assert_eq!(v.branch(), Some("z9hG4bK776asdhds"));
+        assert!(v.has_magic_cookie());
+        assert_eq!(v.received(), Some("192.0.2.1".parse().unwrap()));
+        assert_eq!(v.rport(), Some(Some(5070)));
+        assert_eq!(v.maddr(), Some(Host::Ipv4(Ipv4Addr::new(224, 2, 0, 1))));
+        assert_eq!(v.ttl(), Some(16));
+    }
+
+    #[test]
+    fn via_without_port() {
+        let v = via("SIP / 2.0 / TLS proxy.example.com;branch=1234;rport");
+
+        assert_eq!(v.transport(), "TLS");
+        assert_eq!(v.host(), &Host::Domain("proxy.example.com".to_owned()));
+        assert_eq!(v.port(), None);
+        assert!(!v.has_magic_cookie());
+        assert_eq!(v.rport(), Some(None));
+        assert_eq!(v.received(), None);
+        assert_eq!(
+            v.to_string(),
+            "SIP/2.0/TLS proxy.example.com;branch=1234;rport"
+        );
+    }
+
+    #[test]
+    fn via_ipv6() {
+        let v = via("SIP/2.0/TCP [2001:db8::1];received=2001:db8::9");
+
+        assert_eq!(v.host(), &Host::Ipv6("2001:db8::1".parse().unwrap()));
+        assert_eq!(v.received(), Some("2001:db8::9".parse().unwrap()));
+        assert_eq!(
+            v.to_string(),
+            "SIP/2.0/TCP [2001:db8::1];received=2001:db8::9"
+        );
+    }
+
+    #[test]
+    fn via_server_params() {
+        let mut v = Via::new("UDP", Host::from("10.0.0.1"), Some(5060));
+        v.set_branch("z9hG4bK-1");
+        v.set_rport(None);
+        assert_eq!(
+            v.to_string(),
+            "SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1;rport"
+        );
+
+        v.set_received("192.0.2.4".parse().unwrap());
+        v.set_rport(Some(9988));
+        assert_eq!(
+            v.to_string(),
+            "SIP/2.0/UDP 10.0.0.1:5060;branch=z9hG4bK-1;rport=9988;received=192.0.2.4"
+        );
+    }
+}
@@ -1,10 +1,16 @@
-#[macro_use]
+//failure_derive expands #[derive(Fail)] into impls nested inside anonymous consts
+#![allow(non_local_definitions)]
+
 extern crate nom;
 #[macro_use]
 extern crate failure;
+extern crate md5;
+extern crate sha2;
 
 mod header;
+mod sdp;
 pub use header::*;
+pub use sdp::*;
 
 pub fn is_reserved_char_except(c: u8, except: &[u8]) -> bool {
     !except.contains(&c) && b"()<>@,:;\\/?= \t\r\n".contains(&c)
@@ -26,6 +32,63 @@ pub fn is_any_of(c: u8, s: &[u8]) -> bool {
     s.contains(&c)
 }
 
+pub fn trim_end_spaces(s: &[u8]) -> &[u8] {
+    let len = s.len() - s.iter().rev().take_while(|c| nom::is_space(**c)).count();
+    &s[..len]
+}
+
+pub fn trim_spaces(s: &[u8]) -> &[u8] {
+    let start = s.iter().take_while(|c| nom::is_space(**c)).count();
+    trim_end_spaces(&s[start..])
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Decodes `%XX` escapes. Malformed escapes are kept as they are.
+pub fn percent_decode(s: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut i = 0;
+
+    while i < s.len() {
+        let escaped = match s.get(i + 1..i + 3) {
+            Some(&[h, l]) if s[i] == b'%' => {
+                hex_value(h).and_then(|h| hex_value(l).map(|l| h << 4 | l))
+            }
+            _ => None,
+        };
+
+        match escaped {
+            Some(c) => {
+                decoded.push(c);
+                i += 3;
+            }
+            None => {
+                decoded.push(s[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Escapes as `%XX` every byte not accepted by `allowed`, as well as `%` itself.
+pub fn percent_encode(s: &str, allowed: fn(u8) -> bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for c in s.bytes() {
+        if c != b'%' && allowed(c) {
+            encoded.push(c as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", c));
+        }
+    }
+
+    encoded
+}
+
 pub fn to_str(s: &[u8]) -> Option<String> {
     String::from_utf8(s.to_vec()).ok().filter(|s| !s.is_empty())
 }
@@ -0,0 +1,360 @@
+use super::*;
+
+/// A single `a=` line, either a flag (`a=recvonly`) or a `name:value` pair.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: Option<&str>) -> Attribute {
+        Attribute {
+            name: name.to_owned(),
+            value: value.map(|v| v.to_owned()),
+        }
+    }
+
+    pub fn is_flag(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+impl From<&str> for Attribute {
+    fn from(line: &str) -> Attribute {
+        match line.find(':') {
+            Some(i) => Attribute::new(&line[..i], Some(&line[i + 1..])),
+            None => Attribute::new(line, None),
+        }
+    }
+}
+
+/// Writes the attribute without the `a=` prefix.
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}:{}", self.name, value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Values of the attributes with the given name.
+pub fn attribute_values<'a>(
+    attributes: &'a [Attribute],
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    attributes
+        .iter()
+        .filter(move |a| a.name == name)
+        .filter_map(|a| a.value.as_deref())
+}
+
+/// Media direction attribute (RFC 8866 section 6.7). `SendRecv` is assumed when there is none.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Direction {
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::SendRecv => "sendrecv",
+            Direction::SendOnly => "sendonly",
+            Direction::RecvOnly => "recvonly",
+            Direction::Inactive => "inactive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "sendrecv" => Some(Direction::SendRecv),
+            "sendonly" => Some(Direction::SendOnly),
+            "recvonly" => Some(Direction::RecvOnly),
+            "inactive" => Some(Direction::Inactive),
+            _ => None,
+        }
+    }
+
+    /// Direction seen from the other side, so `sendonly` becomes `recvonly` and vice versa.
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::SendOnly => Direction::RecvOnly,
+            Direction::RecvOnly => Direction::SendOnly,
+            d => d,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Direction given by a list of attributes, if any.
+pub fn find_direction(attributes: &[Attribute]) -> Option<Direction> {
+    attributes
+        .iter()
+        .filter(|a| a.is_flag())
+        .find_map(|a| Direction::from_name(&a.name))
+}
+
+/// Replaces any direction attribute by the given one.
+pub fn replace_direction(attributes: &mut Vec<Attribute>, direction: Direction) {
+    attributes.retain(|a| !a.is_flag() || Direction::from_name(&a.name).is_none());
+    attributes.push(Attribute::new(direction.as_str(), None));
+}
+
+/// Value of `a=rtpmap`, like `96 opus/48000/2`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RtpMap {
+    pub payload: u8,
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u32>,
+}
+
+impl RtpMap {
+    pub fn parse(value: &str) -> Option<RtpMap> {
+        let mut parts = value.splitn(2, ' ');
+        let payload = parts.next()?.parse().ok()?;
+        let mut encoding = parts.next()?.trim().split('/');
+
+        Some(RtpMap {
+            payload,
+            encoding: encoding.next()?.to_owned(),
+            clock_rate: encoding.next()?.parse().ok()?,
+            channels: match encoding.next() {
+                Some(c) => Some(c.parse().ok()?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl fmt::Display for RtpMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}/{}", self.payload, self.encoding, self.clock_rate)?;
+
+        if let Some(channels) = self.channels {
+            write!(f, "/{}", channels)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of `a=fmtp`, like `101 0-15`. The format specific params are kept as they are.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Fmtp {
+    pub format: String,
+    pub params: String,
+}
+
+impl Fmtp {
+    pub fn parse(value: &str) -> Option<Fmtp> {
+        let mut parts = value.splitn(2, ' ');
+
+        Some(Fmtp {
+            format: parts.next()?.to_owned(),
+            params: parts.next()?.trim().to_owned(),
+        })
+    }
+
+    /// Value of a `name=value` param, for formats which use `;` separated params.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.split(';').find_map(|p| {
+            let mut kv = p.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k.eq_ignore_ascii_case(name) => Some(v),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl fmt::Display for Fmtp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.format, self.params)
+    }
+}
+
+/// ICE candidate (RFC 8839 section 5.1), as found on `a=candidate`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Candidate {
+    pub foundation: String,
+    pub component: u32,
+    pub transport: String,
+    pub priority: u32,
+    pub address: String,
+    pub port: u16,
+    pub kind: String,
+    pub related_address: Option<String>,
+    pub related_port: Option<u16>,
+    pub extensions: Vec<(String, String)>,
+}
+
+impl Candidate {
+    pub fn parse(value: &str) -> Option<Candidate> {
+        let mut parts = value.split_whitespace();
+
+        let mut candidate = Candidate {
+            foundation: parts.next()?.to_owned(),
+            component: parts.next()?.parse().ok()?,
+            transport: parts.next()?.to_owned(),
+            priority: parts.next()?.parse().ok()?,
+            address: parts.next()?.to_owned(),
+            port: parts.next()?.parse().ok()?,
+            kind: match parts.next()? {
+                "typ" => parts.next()?.to_owned(),
+                _ => return None,
+            },
+            related_address: None,
+            related_port: None,
+            extensions: vec![],
+        };
+
+        while let Some(name) = parts.next() {
+            let value = parts.next()?;
+
+            match name {
+                "raddr" => candidate.related_address = Some(value.to_owned()),
+                "rport" => candidate.related_port = Some(value.parse().ok()?),
+                _ => candidate
+                    .extensions
+                    .push((name.to_owned(), value.to_owned())),
+            }
+        }
+
+        Some(candidate)
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} typ {}",
+            self.foundation,
+            self.component,
+            self.transport,
+            self.priority,
+            self.address,
+            self.port,
+            self.kind
+        )?;
+
+        if let Some(address) = &self.related_address {
+            write!(f, " raddr {}", address)?;
+        }
+
+        if let Some(port) = self.related_port {
+            write!(f, " rport {}", port)?;
+        }
+
+        for (name, value) in &self.extensions {
+            write!(f, " {} {}", name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// SDES crypto line (RFC 4568 section 9.1), as found on `a=crypto`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Crypto {
+    pub tag: u32,
+    pub suite: String,
+    pub key_params: String,
+    pub session_params: Vec<String>,
+}
+
+impl Crypto {
+    pub fn parse(value: &str) -> Option<Crypto> {
+        let mut parts = value.split_whitespace();
+
+        Some(Crypto {
+            tag: parts.next()?.parse().ok()?,
+            suite: parts.next()?.to_owned(),
+            key_params: parts.next()?.to_owned(),
+            session_params: parts.map(|p| p.to_owned()).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Crypto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.tag, self.suite, self.key_params)?;
+
+        for param in &self.session_params {
+            write!(f, " {}", param)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtpmap_and_fmtp() {
+        let rtpmap = RtpMap::parse("96 opus/48000/2").unwrap();
+        assert_eq!(rtpmap.payload, 96);
+        assert_eq!(rtpmap.encoding, "opus");
+        assert_eq!(rtpmap.clock_rate, 48000);
+        assert_eq!(rtpmap.channels, Some(2));
+        assert_eq!(rtpmap.to_string(), "96 opus/48000/2");
+
+        assert_eq!(RtpMap::parse("0 PCMU").map(|r| r.payload), None);
+        assert_eq!(RtpMap::parse("x PCMU/8000"), None);
+
+        let fmtp = Fmtp::parse("96 minptime=10;useinbandfec=1").unwrap();
+        assert_eq!(fmtp.format, "96");
+        assert_eq!(fmtp.param("useinbandfec"), Some("1"));
+        assert_eq!(fmtp.param("stereo"), None);
+        assert_eq!(fmtp.to_string(), "96 minptime=10;useinbandfec=1");
+    }
+
+    #[test]
+    fn candidate() {
+        let value =
+            "1 1 UDP 1694498815 192.0.2.3 45664 typ srflx raddr 10.0.1.1 rport 8998 generation 0";
+        let candidate = Candidate::parse(value).unwrap();
+
+        assert_eq!(candidate.foundation, "1");
+        assert_eq!(candidate.component, 1);
+        assert_eq!(candidate.priority, 1694498815);
+        assert_eq!(candidate.port, 45664);
+        assert_eq!(candidate.kind, "srflx");
+        assert_eq!(candidate.related_address, Some("10.0.1.1".to_owned()));
+        assert_eq!(candidate.related_port, Some(8998));
+        assert_eq!(
+            candidate.extensions,
+            vec![("generation".to_owned(), "0".to_owned())]
+        );
+        assert_eq!(candidate.to_string(), value);
+
+        assert_eq!(Candidate::parse("1 1 UDP 1 192.0.2.3 45664 host"), None);
+    }
+
+    #[test]
+    fn crypto() {
+        let value = "1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:32 KDR=1";
+        let crypto = Crypto::parse(value).unwrap();
+
+        assert_eq!(crypto.tag, 1);
+        assert_eq!(crypto.suite, "AES_CM_128_HMAC_SHA1_80");
+        assert_eq!(
+            crypto.key_params,
+            "inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:32"
+        );
+        assert_eq!(crypto.session_params, vec!["KDR=1".to_owned()]);
+        assert_eq!(crypto.to_string(), value);
+    }
+}
@@ -0,0 +1,263 @@
+use super::*;
+
+/// Encodings of the static RTP payload types (RFC 3551 section 6), used when there is no
+/// `a=rtpmap` for them.
+pub const STATIC_PAYLOAD_TYPES: &[(u8, &str, u32, Option<u32>)] = &[
+    (0, "PCMU", 8000, Some(1)),
+    (3, "GSM", 8000, Some(1)),
+    (4, "G723", 8000, Some(1)),
+    (8, "PCMA", 8000, Some(1)),
+    (9, "G722", 8000, Some(1)),
+    (13, "CN", 8000, Some(1)),
+    (18, "G729", 8000, Some(1)),
+    (26, "JPEG", 90000, None),
+    (31, "H261", 90000, None),
+    (34, "H263", 90000, None),
+];
+
+/// An RTP payload format of a media, joining its `a=rtpmap` and `a=fmtp`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Codec {
+    pub payload: u8,
+    pub encoding: String,
+    pub clock_rate: u32,
+    pub channels: Option<u32>,
+    pub fmtp: Option<String>,
+}
+
+impl Codec {
+    /// Whether both codecs have the same encoding, clock rate and channels, regardless of the
+    /// payload type each side chose for it.
+    pub fn matches(&self, other: &Codec) -> bool {
+        self.encoding.eq_ignore_ascii_case(&other.encoding)
+            && self.clock_rate == other.clock_rate
+            && self.channels.unwrap_or(1) == other.channels.unwrap_or(1)
+    }
+
+    pub fn rtpmap(&self) -> RtpMap {
+        RtpMap {
+            payload: self.payload,
+            encoding: self.encoding.clone(),
+            clock_rate: self.clock_rate,
+            channels: self.channels,
+        }
+    }
+}
+
+/// A media section, starting at a `m=` line and going until the next one.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MediaDescription {
+    pub media: String,
+    pub port: u16,
+    pub num_ports: Option<u16>,
+    pub protocol: String,
+    pub formats: Vec<String>,
+    pub title: Option<String>,
+    pub connections: Vec<Connection>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub key: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl MediaDescription {
+    pub fn new(media: &str, port: u16, protocol: &str) -> MediaDescription {
+        MediaDescription {
+            media: media.to_owned(),
+            port,
+            num_ports: None,
+            protocol: protocol.to_owned(),
+            formats: vec![],
+            title: None,
+            connections: vec![],
+            bandwidths: vec![],
+            key: None,
+            attributes: vec![],
+        }
+    }
+
+    /// Parses the value of a `m=` line, like `audio 49170 RTP/AVP 0 8`.
+    pub fn parse(value: &str) -> Option<MediaDescription> {
+        let mut parts = value.split_whitespace();
+        let media = parts.next()?;
+        let mut port = parts.next()?.splitn(2, '/');
+
+        let mut description = MediaDescription::new(media, port.next()?.parse().ok()?, "");
+        description.num_ports = match port.next() {
+            Some(n) => Some(n.parse().ok()?),
+            None => None,
+        };
+        description.protocol = parts.next()?.to_owned();
+        description.formats = parts.map(|f| f.to_owned()).collect();
+
+        Some(description)
+    }
+
+    /// A media with port zero was rejected, or is disabled, by the offerer.
+    pub fn is_rejected(&self) -> bool {
+        self.port == 0
+    }
+
+    /// Whether the formats are RTP payload types.
+    pub fn is_rtp(&self) -> bool {
+        self.protocol.contains("RTP/")
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    /// Value of the first attribute with the given name. `None` for flags.
+    pub fn attribute_value(&self, name: &str) -> Option<&str> {
+        self.attribute(name).and_then(|a| a.value.as_deref())
+    }
+
+    /// Direction set on this media. Use `SessionDescription::media_direction` to also consider
+    /// the session level one.
+    pub fn direction(&self) -> Option<Direction> {
+        find_direction(&self.attributes)
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        replace_direction(&mut self.attributes, direction);
+    }
+
+    pub fn rtpmap(&self, payload: u8) -> Option<RtpMap> {
+        attribute_values(&self.attributes, "rtpmap")
+            .filter_map(RtpMap::parse)
+            .find(|r| r.payload == payload)
+    }
+
+    pub fn fmtp(&self, format: &str) -> Option<Fmtp> {
+        attribute_values(&self.attributes, "fmtp")
+            .filter_map(Fmtp::parse)
+            .find(|f| f.format == format)
+    }
+
+    /// Codecs in the order of preference given by the formats. Static payload types without
+    /// `a=rtpmap` use their well-known encoding, while dynamic ones without it are skipped.
+    pub fn codecs(&self) -> Vec<Codec> {
+        if !self.is_rtp() {
+            return vec![];
+        }
+
+        self.formats
+            .iter()
+            .filter_map(|format| {
+                let payload = format.parse().ok()?;
+                let rtpmap = self.rtpmap(payload).or_else(|| {
+                    STATIC_PAYLOAD_TYPES
+                        .iter()
+                        .find(|p| p.0 == payload)
+                        .map(|p| RtpMap {
+                            payload,
+                            encoding: p.1.to_owned(),
+                            clock_rate: p.2,
+                            channels: p.3,
+                        })
+                })?;
+
+                Some(Codec {
+                    payload,
+                    encoding: rtpmap.encoding,
+                    clock_rate: rtpmap.clock_rate,
+                    channels: rtpmap.channels,
+                    fmtp: self.fmtp(format).map(|f| f.params),
+                })
+            })
+            .collect()
+    }
+
+    pub fn candidates(&self) -> Vec<Candidate> {
+        attribute_values(&self.attributes, "candidate")
+            .filter_map(Candidate::parse)
+            .collect()
+    }
+
+    pub fn cryptos(&self) -> Vec<Crypto> {
+        attribute_values(&self.attributes, "crypto")
+            .filter_map(Crypto::parse)
+            .collect()
+    }
+}
+
+/// Writes the whole media section, starting at the `m=` line, with a CRLF after each line.
+impl fmt::Display for MediaDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m={} {}", self.media, self.port)?;
+
+        if let Some(num_ports) = self.num_ports {
+            write!(f, "/{}", num_ports)?;
+        }
+
+        write!(f, " {}", self.protocol)?;
+
+        for format in &self.formats {
+            write!(f, " {}", format)?;
+        }
+
+        write!(f, "\r\n")?;
+
+        if let Some(title) = &self.title {
+            write!(f, "i={}\r\n", title)?;
+        }
+
+        for connection in &self.connections {
+            write!(f, "c={}\r\n", connection)?;
+        }
+
+        for bandwidth in &self.bandwidths {
+            write!(f, "b={}\r\n", bandwidth)?;
+        }
+
+        if let Some(key) = &self.key {
+            write!(f, "k={}\r\n", key)?;
+        }
+
+        for attribute in &self.attributes {
+            write!(f, "a={}\r\n", attribute)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_line() {
+        let media = MediaDescription::parse("video 49170/2 RTP/AVP 31 96").unwrap();
+
+        assert_eq!(media.media, "video");
+        assert_eq!(media.port, 49170);
+        assert_eq!(media.num_ports, Some(2));
+        assert_eq!(media.protocol, "RTP/AVP");
+        assert_eq!(media.formats, vec!["31", "96"]);
+        assert_eq!(media.to_string(), "m=video 49170/2 RTP/AVP 31 96\r\n");
+
+        assert_eq!(MediaDescription::parse("audio x RTP/AVP 0"), None);
+    }
+
+    #[test]
+    fn codecs_and_direction() {
+        let mut media = MediaDescription::parse("audio 49170 RTP/AVP 0 96 101 97").unwrap();
+        media.attributes = vec![
+            Attribute::from("rtpmap:96 opus/48000/2"),
+            Attribute::from("rtpmap:101 telephone-event/8000"),
+            Attribute::from("fmtp:101 0-15"),
+            Attribute::from("sendonly"),
+        ];
+
+        let codecs = media.codecs();
+        assert_eq!(codecs.len(), 3);
+        assert_eq!(codecs[0].encoding, "PCMU");
+        assert_eq!(codecs[1].channels, Some(2));
+        assert_eq!(codecs[2].fmtp, Some("0-15".to_owned()));
+
+        assert_eq!(media.direction(), Some(Direction::SendOnly));
+        media.set_direction(Direction::Inactive);
+        assert_eq!(media.direction(), Some(Direction::Inactive));
+        assert_eq!(media.attributes.len(), 4);
+    }
+}
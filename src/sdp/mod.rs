@@ -0,0 +1,557 @@
+use super::*;
+use std::fmt;
+
+mod attributes;
+mod media;
+mod negotiation;
+pub use self::attributes::*;
+pub use self::media::*;
+pub use self::negotiation::*;
+
+/// Value of the `o=` line, which identifies the session and its version.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Origin {
+    pub username: String,
+    pub session_id: u64,
+    pub session_version: u64,
+    pub net_type: String,
+    pub addr_type: String,
+    pub address: String,
+}
+
+impl Origin {
+    pub fn parse(value: &str) -> Option<Origin> {
+        let mut parts = value.split_whitespace();
+
+        let origin = Origin {
+            username: parts.next()?.to_owned(),
+            session_id: parts.next()?.parse().ok()?,
+            session_version: parts.next()?.parse().ok()?,
+            net_type: parts.next()?.to_owned(),
+            addr_type: parts.next()?.to_owned(),
+            address: parts.next()?.to_owned(),
+        };
+
+        if parts.next().is_some() {
+            None
+        } else {
+            Some(origin)
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.username,
+            self.session_id,
+            self.session_version,
+            self.net_type,
+            self.addr_type,
+            self.address
+        )
+    }
+}
+
+/// Value of a `c=` line, like `IN IP4 224.2.1.1/127/3`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Connection {
+    pub net_type: String,
+    pub addr_type: String,
+    pub address: String,
+    pub ttl: Option<u8>,
+    pub num_addresses: Option<u32>,
+}
+
+impl Connection {
+    pub fn new(addr_type: &str, address: &str) -> Connection {
+        Connection {
+            net_type: "IN".to_owned(),
+            addr_type: addr_type.to_owned(),
+            address: address.to_owned(),
+            ttl: None,
+            num_addresses: None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Connection> {
+        let mut parts = value.split_whitespace();
+        let net_type = parts.next()?;
+        let addr_type = parts.next()?;
+        let mut address = parts.next()?.split('/');
+
+        let mut connection = Connection::new(addr_type, address.next()?);
+        connection.net_type = net_type.to_owned();
+
+        //IPv6 multicast has no TTL, so its only suffix is the number of addresses
+        match (address.next(), address.next()) {
+            (None, _) => (),
+            (Some(n), None) if addr_type == "IP6" => {
+                connection.num_addresses = Some(n.parse().ok()?)
+            }
+            (Some(ttl), n) => {
+                connection.ttl = Some(ttl.parse().ok()?);
+                connection.num_addresses = match n {
+                    Some(n) => Some(n.parse().ok()?),
+                    None => None,
+                };
+            }
+        }
+
+        if parts.next().is_some() {
+            None
+        } else {
+            Some(connection)
+        }
+    }
+}
+
+impl fmt::Display for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.net_type, self.addr_type, self.address)?;
+
+        if let Some(ttl) = self.ttl {
+            write!(f, "/{}", ttl)?;
+        }
+
+        if let Some(num_addresses) = self.num_addresses {
+            write!(f, "/{}", num_addresses)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of a `b=` line, like `AS:128`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Bandwidth {
+    pub bw_type: String,
+    pub bandwidth: u32,
+}
+
+impl Bandwidth {
+    pub fn parse(value: &str) -> Option<Bandwidth> {
+        let mut parts = value.splitn(2, ':');
+
+        Some(Bandwidth {
+            bw_type: parts.next()?.to_owned(),
+            bandwidth: parts.next()?.trim().parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.bw_type, self.bandwidth)
+    }
+}
+
+/// A `t=` line and the `r=` lines repeating it, which are kept as they are.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Timing {
+    pub start: u64,
+    pub stop: u64,
+    pub repeats: Vec<String>,
+}
+
+impl Timing {
+    pub fn parse(value: &str) -> Option<Timing> {
+        let mut parts = value.split_whitespace();
+
+        Some(Timing {
+            start: parts.next()?.parse().ok()?,
+            stop: parts.next()?.parse().ok()?,
+            repeats: vec![],
+        })
+    }
+}
+
+/// Writes the `t=` value and its `r=` lines, without the last CRLF.
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.start, self.stop)?;
+
+        for repeat in &self.repeats {
+            write!(f, "\r\nr={}", repeat)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A whole session description, with its session level lines and media sections.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SessionDescription {
+    pub version: u32,
+    pub origin: Origin,
+    pub session_name: String,
+    pub information: Option<String>,
+    pub uri: Option<String>,
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub connection: Option<Connection>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub timings: Vec<Timing>,
+    pub time_zones: Option<String>,
+    pub key: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub media: Vec<MediaDescription>,
+}
+
+impl SessionDescription {
+    /// An empty session, with `t=0 0` and the given origin.
+    pub fn new(origin: Origin) -> SessionDescription {
+        SessionDescription {
+            version: 0,
+            origin,
+            session_name: "-".to_owned(),
+            information: None,
+            uri: None,
+            emails: vec![],
+            phones: vec![],
+            connection: None,
+            bandwidths: vec![],
+            timings: vec![Timing {
+                start: 0,
+                stop: 0,
+                repeats: vec![],
+            }],
+            time_zones: None,
+            key: None,
+            attributes: vec![],
+            media: vec![],
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    /// Value of the first session level attribute with the given name. `None` for flags.
+    pub fn attribute_value(&self, name: &str) -> Option<&str> {
+        self.attribute(name).and_then(|a| a.value.as_deref())
+    }
+
+    /// Session level direction, if any.
+    pub fn direction(&self) -> Option<Direction> {
+        find_direction(&self.attributes)
+    }
+
+    /// Direction of a media, falling back to the session level one and then to `sendrecv`.
+    pub fn media_direction(&self, media: &MediaDescription) -> Direction {
+        media
+            .direction()
+            .or_else(|| self.direction())
+            .unwrap_or(Direction::SendRecv)
+    }
+
+    /// Connection of a media, falling back to the session level one.
+    pub fn media_connection<'a>(&'a self, media: &'a MediaDescription) -> Option<&'a Connection> {
+        media.connections.first().or(self.connection.as_ref())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+/// Writes the session description, with a CRLF after each line.
+impl fmt::Display for SessionDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v={}\r\n", self.version)?;
+        write!(f, "o={}\r\n", self.origin)?;
+        write!(f, "s={}\r\n", self.session_name)?;
+
+        if let Some(information) = &self.information {
+            write!(f, "i={}\r\n", information)?;
+        }
+
+        if let Some(uri) = &self.uri {
+            write!(f, "u={}\r\n", uri)?;
+        }
+
+        for email in &self.emails {
+            write!(f, "e={}\r\n", email)?;
+        }
+
+        for phone in &self.phones {
+            write!(f, "p={}\r\n", phone)?;
+        }
+
+        if let Some(connection) = &self.connection {
+            write!(f, "c={}\r\n", connection)?;
+        }
+
+        for bandwidth in &self.bandwidths {
+            write!(f, "b={}\r\n", bandwidth)?;
+        }
+
+        for timing in &self.timings {
+            write!(f, "t={}\r\n", timing)?;
+        }
+
+        if let Some(time_zones) = &self.time_zones {
+            write!(f, "z={}\r\n", time_zones)?;
+        }
+
+        if let Some(key) = &self.key {
+            write!(f, "k={}\r\n", key)?;
+        }
+
+        for attribute in &self.attributes {
+            write!(f, "a={}\r\n", attribute)?;
+        }
+
+        for media in &self.media {
+            write!(f, "{}", media)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn sdp_error(line: &str, detail: &str) -> MessageParserError {
+    MessageParserError::Sdp {
+        line: line.to_owned(),
+        detail: detail.to_owned(),
+    }
+}
+
+//Parses a line value with one of the `parse` functions, failing with the line on error
+fn parse_value<T, F: Fn(&str) -> Option<T>>(line: &str, value: &str, parse: F) -> SipResult<T> {
+    parse(value).ok_or_else(|| sdp_error(line, "invalid value"))
+}
+
+/// Parses a session description. Lines may end with CRLF or just LF, as RFC 8866 asks parsers
+/// to accept both. Unknown line types make the whole description invalid.
+pub fn parse_sdp(data: &[u8]) -> SipResult<SessionDescription> {
+    let text = String::from_utf8_lossy(data);
+    let mut lines = text
+        .split('\n')
+        .map(|l| l.trim_end_matches('\r'))
+        .filter(|l| !l.is_empty());
+
+    let mut next_line = || -> SipResult<Option<(&str, char, &str)>> {
+        match lines.next() {
+            Some(line) => {
+                //Checked on bytes, since a replaced invalid byte takes more than one
+                match line.as_bytes() {
+                    [kind, b'=', ..] if kind.is_ascii() => {
+                        Ok(Some((line, *kind as char, &line[2..])))
+                    }
+                    _ => Err(sdp_error(line, "expected <type>=<value>")),
+                }
+            }
+            None => Ok(None),
+        }
+    };
+
+    let version = match next_line()? {
+        Some((line, 'v', value)) => parse_value(line, value, |v| v.trim().parse().ok())?,
+        _ => return Err(sdp_error("", "missing v= line")),
+    };
+
+    let origin = match next_line()? {
+        Some((line, 'o', value)) => parse_value(line, value, Origin::parse)?,
+        _ => return Err(sdp_error("", "missing o= line")),
+    };
+
+    let mut sdp = SessionDescription::new(origin);
+    sdp.version = version;
+    sdp.timings.clear();
+
+    sdp.session_name = match next_line()? {
+        Some((_, 's', value)) => value.to_owned(),
+        _ => return Err(sdp_error("", "missing s= line")),
+    };
+
+    while let Some((line, kind, value)) = next_line()? {
+        if let Some(media) = sdp.media.last_mut() {
+            match kind {
+                'm' => (),
+                'i' => media.title = Some(value.to_owned()),
+                'c' => media
+                    .connections
+                    .push(parse_value(line, value, Connection::parse)?),
+                'b' => media
+                    .bandwidths
+                    .push(parse_value(line, value, Bandwidth::parse)?),
+                'k' => media.key = Some(value.to_owned()),
+                'a' => media.attributes.push(Attribute::from(value)),
+                _ => return Err(sdp_error(line, "unexpected line on media section")),
+            }
+
+            if kind != 'm' {
+                continue;
+            }
+        }
+
+        match kind {
+            'i' => sdp.information = Some(value.to_owned()),
+            'u' => sdp.uri = Some(value.to_owned()),
+            'e' => sdp.emails.push(value.to_owned()),
+            'p' => sdp.phones.push(value.to_owned()),
+            'c' => sdp.connection = Some(parse_value(line, value, Connection::parse)?),
+            'b' => sdp
+                .bandwidths
+                .push(parse_value(line, value, Bandwidth::parse)?),
+            't' => sdp.timings.push(parse_value(line, value, Timing::parse)?),
+            'r' => match sdp.timings.last_mut() {
+                Some(timing) => timing.repeats.push(value.to_owned()),
+                None => return Err(sdp_error(line, "r= line without t= line")),
+            },
+            'z' => sdp.time_zones = Some(value.to_owned()),
+            'k' => sdp.key = Some(value.to_owned()),
+            'a' => sdp.attributes.push(Attribute::from(value)),
+            'm' => sdp
+                .media
+                .push(parse_value(line, value, MediaDescription::parse)?),
+            _ => return Err(sdp_error(line, "unexpected line")),
+        }
+    }
+
+    Ok(sdp)
+}
+
+impl SipMessage {
+    /// Whether the body is a session description, according to `Content-Type`.
+    pub fn has_sdp(&self) -> bool {
+        match self.headers.first("Content-Type") {
+            Some(SipHeader::ContentType(content_type)) => content_type
+                .split(';')
+                .next()
+                .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/sdp")),
+            _ => false,
+        }
+    }
+
+    /// Parses the body as a session description. `None` when the body is empty or isn't SDP.
+    pub fn sdp(&self) -> Option<SipResult<SessionDescription>> {
+        if self.body.is_empty() || !self.has_sdp() {
+            None
+        } else {
+            Some(parse_sdp(&self.body))
+        }
+    }
+
+    /// Replaces the body by a session description, setting `Content-Type` and `Content-Length`.
+    pub fn set_sdp(&mut self, sdp: &SessionDescription) {
+        self.headers
+            .set(SipHeader::ContentType("application/sdp".to_owned()));
+        self.set_body(sdp.to_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Example from RFC 8866 section 5, with a few more attributes
+    const SDP: &str = "v=0\r\n\
+        o=jdoe 3724394400 3724394405 IN IP4 198.51.100.1\r\n\
+        s=Call to John Smith\r\n\
+        i=SDP Offer #1\r\n\
+        u=http://www.jdoe.example.com/home.html\r\n\
+        e=Jane Doe <jane@jdoe.example.com>\r\n\
+        p=+1 617 555-6011\r\n\
+        c=IN IP4 198.51.100.1\r\n\
+        t=0 0\r\n\
+        a=recvonly\r\n\
+        m=audio 49170 RTP/AVP 0\r\n\
+        m=audio 49180 RTP/AVP 0\r\n\
+        m=video 51372 RTP/AVP 99\r\n\
+        c=IN IP6 2001:db8::2\r\n\
+        a=rtpmap:99 h263-1998/90000\r\n";
+
+    #[test]
+    fn sdp_round_trip() {
+        let sdp = parse_sdp(SDP.as_bytes()).unwrap();
+
+        assert_eq!(sdp.origin.username, "jdoe");
+        assert_eq!(sdp.origin.session_version, 3724394405);
+        assert_eq!(sdp.session_name, "Call to John Smith");
+        assert_eq!(sdp.emails, vec!["Jane Doe <jane@jdoe.example.com>"]);
+        assert_eq!(
+            sdp.connection.as_ref().map(|c| c.address.as_str()),
+            Some("198.51.100.1")
+        );
+        assert_eq!(sdp.media.len(), 3);
+
+        let video = &sdp.media[2];
+        assert_eq!(video.rtpmap(99).map(|r| r.clock_rate), Some(90000));
+        assert_eq!(
+            sdp.media_connection(video).map(|c| c.address.as_str()),
+            Some("2001:db8::2")
+        );
+        assert_eq!(
+            sdp.media_connection(&sdp.media[0])
+                .map(|c| c.address.as_str()),
+            Some("198.51.100.1")
+        );
+        assert_eq!(sdp.media_direction(video), Direction::RecvOnly);
+
+        assert_eq!(sdp.to_string(), SDP);
+        assert_eq!(parse_sdp(sdp.to_string().as_bytes()).unwrap(), sdp);
+    }
+
+    #[test]
+    fn sdp_lf_and_connection_suffixes() {
+        let data = "v=0\n\
+            o=- 1 1 IN IP4 192.0.2.1\n\
+            s=-\n\
+            c=IN IP4 224.2.1.1/127/3\n\
+            t=3034423619 3042462419\n\
+            r=604800 3600 0 90000\n\
+            m=audio 0 RTP/AVP 0\n\
+            c=IN IP6 ff15::101/3\n";
+        let sdp = parse_sdp(data.as_bytes()).unwrap();
+
+        let connection = sdp.connection.as_ref().unwrap();
+        assert_eq!(connection.ttl, Some(127));
+        assert_eq!(connection.num_addresses, Some(3));
+        assert_eq!(sdp.media[0].connections[0].ttl, None);
+        assert_eq!(sdp.media[0].connections[0].num_addresses, Some(3));
+        assert_eq!(sdp.timings[0].repeats, vec!["604800 3600 0 90000"]);
+        assert!(sdp.media[0].is_rejected());
+    }
+
+    #[test]
+    fn sdp_errors() {
+        let invalid: &[&[u8]] = &[
+            b"o=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\n",
+            b"v=0\r\ns=-\r\n",
+            b"v=0\r\no=- x 1 IN IP4 192.0.2.1\r\ns=-\r\n",
+            b"v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nx=1\r\n",
+            b"v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nm=audio 1 RTP/AVP 0\r\nu=http://a\r\n",
+            b"v=0\r\no=- 1 1 IN IP4 192.0.2.1\r\ns=-\r\nbad line\r\n",
+            b"v=0\r\no=- 1 1 IN IP4 1.2.3.4\r\ns=-\r\n\xff=x\r\n",
+        ];
+
+        for sdp in invalid.iter() {
+            assert!(parse_sdp(sdp).is_err(), "{}", String::from_utf8_lossy(sdp));
+        }
+    }
+
+    #[test]
+    fn message_sdp() {
+        let data = format!(
+            "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+             Content-Type: application/sdp\r\n\
+             Content-Length: {}\r\n\
+             \r\n\
+             {}",
+            SDP.len(),
+            SDP
+        );
+        let msg = MessageParser::new(data.as_bytes()).next_message().unwrap();
+
+        assert!(msg.has_sdp());
+        assert_eq!(msg.sdp().unwrap().unwrap().media.len(), 3);
+
+        let data: &[u8] = b"OPTIONS sip:bob@biloxi.com SIP/2.0\r\nContent-Length: 0\r\n\r\n";
+        let msg = MessageParser::new(data).next_message().unwrap();
+        assert!(msg.sdp().is_none());
+    }
+}
@@ -0,0 +1,349 @@
+use super::*;
+
+/// What the local side can do for one kind of media, like `audio`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct MediaCapability {
+    pub media: String,
+    pub protocol: String,
+    pub port: u16,
+    pub codecs: Vec<Codec>,
+    pub direction: Direction,
+}
+
+impl MediaCapability {
+    pub fn new(media: &str, port: u16, codecs: Vec<Codec>) -> MediaCapability {
+        MediaCapability {
+            media: media.to_owned(),
+            protocol: "RTP/AVP".to_owned(),
+            port,
+            codecs,
+            direction: Direction::SendRecv,
+        }
+    }
+
+    //Media section with the given codecs, as they must be written on the wire
+    fn media_description(&self, codecs: &[Codec], direction: Direction) -> MediaDescription {
+        let mut media = MediaDescription::new(&self.media, self.port, &self.protocol);
+
+        for codec in codecs {
+            media.formats.push(codec.payload.to_string());
+            media
+                .attributes
+                .push(Attribute::new("rtpmap", Some(&codec.rtpmap().to_string())));
+
+            if let Some(fmtp) = &codec.fmtp {
+                media.attributes.push(Attribute::new(
+                    "fmtp",
+                    Some(&format!("{} {}", codec.payload, fmtp)),
+                ));
+            }
+        }
+
+        media.set_direction(direction);
+        media
+    }
+}
+
+/// Local capability set used to create offers and to answer the ones received (RFC 3264).
+///
+/// Each capability is used by a single media section, since it has its own port, so an offer
+/// with two audio streams gets the second one rejected unless there are two audio capabilities.
+#[derive(PartialEq, Debug, Clone)]
+pub struct LocalCapabilities {
+    pub origin: Origin,
+    pub connection: Connection,
+    pub media: Vec<MediaCapability>,
+}
+
+impl LocalCapabilities {
+    pub fn new(origin: Origin, connection: Connection) -> LocalCapabilities {
+        LocalCapabilities {
+            origin,
+            connection,
+            media: vec![],
+        }
+    }
+
+    /// Offer with every capability. When it replaces a `previous` offer or answer of ours, its
+    /// origin is kept and the version is incremented if anything changed.
+    pub fn offer(&self, previous: Option<&SessionDescription>) -> SessionDescription {
+        let mut offer = SessionDescription::new(self.origin.clone());
+        offer.connection = Some(self.connection.clone());
+        offer.media = self
+            .media
+            .iter()
+            .map(|m| m.media_description(&m.codecs, m.direction))
+            .collect();
+
+        if let Some(previous) = previous {
+            offer.update_origin(previous);
+        }
+
+        offer
+    }
+
+    /// Answer to `offer`, with the same media sections in the same order (RFC 3264 section 6).
+    ///
+    /// Codecs are the intersection of both sides, in the order of the local preference but with
+    /// the payload types of the offer. The direction of each media is the reverse of the offered
+    /// one, restricted by the local capability. Media offered with port zero, without a
+    /// capability of the same protocol or without any common codec, are rejected with port zero.
+    /// `previous` is the last session description we sent in this session, if any, handled as on
+    /// `offer`.
+    pub fn answer(
+        &self,
+        offer: &SessionDescription,
+        previous: Option<&SessionDescription>,
+    ) -> SessionDescription {
+        let mut answer = SessionDescription::new(self.origin.clone());
+        answer.connection = Some(self.connection.clone());
+        answer.timings = offer.timings.clone();
+
+        let mut used = vec![false; self.media.len()];
+
+        for offered in &offer.media {
+            //Another protocol can't be answered, like SRTP offers without the keys to answer them
+            let capability = self.media.iter().enumerate().position(|(i, m)| {
+                !used[i]
+                    && !offered.is_rejected()
+                    && m.media == offered.media
+                    && m.protocol == offered.protocol
+            });
+
+            let media = capability.and_then(|i| {
+                let media = self.answer_media(offer, offered, &self.media[i])?;
+                used[i] = true;
+                Some(media)
+            });
+
+            answer
+                .media
+                .push(media.unwrap_or_else(|| rejected_media(offered)));
+        }
+
+        if let Some(previous) = previous {
+            answer.update_origin(previous);
+        }
+
+        answer
+    }
+
+    fn answer_media(
+        &self,
+        offer: &SessionDescription,
+        offered: &MediaDescription,
+        capability: &MediaCapability,
+    ) -> Option<MediaDescription> {
+        let offered_codecs = offered.codecs();
+        let codecs = capability
+            .codecs
+            .iter()
+            .filter_map(|local| {
+                let remote = offered_codecs.iter().find(|c| c.matches(local))?;
+
+                Some(Codec {
+                    payload: remote.payload,
+                    fmtp: local.fmtp.clone().or_else(|| remote.fmtp.clone()),
+                    ..local.clone()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if codecs.is_empty() {
+            return None;
+        }
+
+        let direction = intersect(
+            offer.media_direction(offered).reverse(),
+            capability.direction,
+        );
+
+        Some(capability.media_description(&codecs, direction))
+    }
+}
+
+//Direction allowed by both the reversed offer and the local capability
+fn intersect(a: Direction, b: Direction) -> Direction {
+    let sends = |d| d == Direction::SendRecv || d == Direction::SendOnly;
+    let receives = |d| d == Direction::SendRecv || d == Direction::RecvOnly;
+
+    match (sends(a) && sends(b), receives(a) && receives(b)) {
+        (true, true) => Direction::SendRecv,
+        (true, false) => Direction::SendOnly,
+        (false, true) => Direction::RecvOnly,
+        (false, false) => Direction::Inactive,
+    }
+}
+
+//Rejected media keeps the offered formats, since a `m=` line needs at least one
+fn rejected_media(offered: &MediaDescription) -> MediaDescription {
+    let mut media = MediaDescription::new(&offered.media, 0, &offered.protocol);
+    media.formats = offered.formats.clone();
+    media
+}
+
+impl SessionDescription {
+    /// Makes this description the next version of `previous`, which we sent earlier in the same
+    /// session. The origin is kept, and its version is incremented only when something changed
+    /// (RFC 3264 section 8).
+    pub fn update_origin(&mut self, previous: &SessionDescription) {
+        self.origin = previous.origin.clone();
+
+        if self != previous {
+            self.origin.session_version = previous.origin.session_version.wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codec(payload: u8, encoding: &str, clock_rate: u32) -> Codec {
+        Codec {
+            payload,
+            encoding: encoding.to_owned(),
+            clock_rate,
+            channels: None,
+            fmtp: None,
+        }
+    }
+
+    fn local() -> LocalCapabilities {
+        let origin = Origin::parse("- 8000 1 IN IP4 192.0.2.20").unwrap();
+        let mut local = LocalCapabilities::new(origin, Connection::new("IP4", "192.0.2.20"));
+
+        local.media.push(MediaCapability::new(
+            "audio",
+            20000,
+            vec![
+                codec(8, "PCMA", 8000),
+                codec(0, "PCMU", 8000),
+                codec(100, "telephone-event", 8000),
+            ],
+        ));
+
+        local
+    }
+
+    const OFFER: &str = "v=0\r\n\
+        o=alice 2890844526 2890844526 IN IP4 192.0.2.10\r\n\
+        s=-\r\n\
+        c=IN IP4 192.0.2.10\r\n\
+        t=0 0\r\n\
+        a=sendonly\r\n\
+        m=audio 49170 RTP/AVP 0 18 101\r\n\
+        a=rtpmap:101 telephone-event/8000\r\n\
+        a=fmtp:101 0-15\r\n\
+        m=video 51372 RTP/AVP 31\r\n\
+        m=audio 0 RTP/AVP 8\r\n";
+
+    #[test]
+    fn answer_offer() {
+        let offer = parse_sdp(OFFER.as_bytes()).unwrap();
+        let answer = local().answer(&offer, None);
+
+        assert_eq!(
+            answer.to_string(),
+            "v=0\r\n\
+             o=- 8000 1 IN IP4 192.0.2.20\r\n\
+             s=-\r\n\
+             c=IN IP4 192.0.2.20\r\n\
+             t=0 0\r\n\
+             m=audio 20000 RTP/AVP 0 101\r\n\
+             a=rtpmap:0 PCMU/8000\r\n\
+             a=rtpmap:101 telephone-event/8000\r\n\
+             a=fmtp:101 0-15\r\n\
+             a=recvonly\r\n\
+             m=video 0 RTP/AVP 31\r\n\
+             m=audio 0 RTP/AVP 8\r\n"
+        );
+    }
+
+    #[test]
+    fn answer_other_protocol() {
+        let offer = OFFER.replace(
+            "m=audio 49170 RTP/AVP 0 18 101\r\n",
+            "m=audio 49170 RTP/SAVP 0 18 101\r\n\
+             a=crypto:1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR\r\n",
+        );
+        let offer = parse_sdp(offer.as_bytes()).unwrap();
+        let answer = local().answer(&offer, None);
+
+        assert_eq!(
+            answer.media[0].to_string(),
+            "m=audio 0 RTP/SAVP 0 18 101\r\n"
+        );
+    }
+
+    #[test]
+    fn answer_directions() {
+        let mut local = local();
+        local.media[0].direction = Direction::SendOnly;
+
+        let mut offer = parse_sdp(OFFER.as_bytes()).unwrap();
+        assert_eq!(
+            local.answer(&offer, None).media[0].direction(),
+            Some(Direction::Inactive)
+        );
+
+        offer.attributes.clear();
+        assert_eq!(
+            local.answer(&offer, None).media[0].direction(),
+            Some(Direction::SendOnly)
+        );
+
+        offer.media[0].set_direction(Direction::Inactive);
+        assert_eq!(
+            local.answer(&offer, None).media[0].direction(),
+            Some(Direction::Inactive)
+        );
+    }
+
+    #[test]
+    fn versions_on_reoffer() {
+        let mut local = local();
+        let first = local.offer(None);
+        assert_eq!(first.origin.session_version, 1);
+
+        //Nothing changed, so the version is kept
+        let same = local.offer(Some(&first));
+        assert_eq!(same, first);
+
+        local.media[0].direction = Direction::SendOnly;
+        local.origin.session_id = 1234;
+        let hold = local.offer(Some(&first));
+
+        assert_eq!(hold.origin.session_id, 8000);
+        assert_eq!(hold.origin.session_version, 2);
+        assert_eq!(hold.media[0].direction(), Some(Direction::SendOnly));
+
+        let offer = parse_sdp(OFFER.as_bytes()).unwrap();
+        let answer = local.answer(&offer, Some(&hold));
+        assert_eq!(answer.origin.session_version, 3);
+        assert_eq!(local.answer(&offer, Some(&answer)), answer);
+    }
+
+    #[test]
+    fn answer_message() {
+        let data = format!(
+            "INVITE sip:bob@biloxi.com SIP/2.0\r\n\
+             Content-Type: application/sdp\r\n\
+             Content-Length: {}\r\n\
+             \r\n\
+             {}",
+            OFFER.len(),
+            OFFER
+        );
+        let invite = MessageParser::new(data.as_bytes()).next_message().unwrap();
+        let offer = invite.sdp().unwrap().unwrap();
+
+        let response: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let mut ok = MessageParser::new(response).next_message().unwrap();
+        ok.set_sdp(&local().answer(&offer, None));
+
+        assert!(ok.has_sdp());
+        assert_eq!(ok.content_length(), Some(ok.body.len() as u32));
+        assert_eq!(ok.sdp().unwrap().unwrap().media.len(), 3);
+    }
+}